@@ -0,0 +1,245 @@
+use crate::{
+    notion_to_md::{AssetKind, AssetRef},
+    utils,
+};
+use anyhow::{Context, Result};
+use futures::{stream, StreamExt};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// 保存したファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadedAsset {
+    pub asset: AssetRef,
+    pub path: PathBuf,
+    /// 画像のヘッダーから読んだ (幅, 高さ)。画像でないか、形式が分からなければ `None`
+    pub dimensions: Option<(u32, u32)>,
+}
+
+/// ダウンロードの結果。失敗したファイルがあっても残りのファイルは保存する
+#[derive(Debug, Default)]
+pub struct DownloadReport {
+    pub downloaded: Vec<DownloadedAsset>,
+    pub failed: Vec<(AssetRef, anyhow::Error)>,
+}
+
+impl DownloadReport {
+    /// 元の URL のファイルを保存したパス。`rewrite_urls` で Markdown の参照を書き換えるのに使う
+    pub fn path_of(&self, url: &str) -> Option<&Path> {
+        self.downloaded
+            .iter()
+            .find(|downloaded| downloaded.asset.url == url)
+            .map(|downloaded| downloaded.path.as_path())
+    }
+
+    /// 幅と高さが分かった画像の、元の URL → (幅, 高さ)。
+    /// `NotionToMarkdownBuilder::image_dimensions` に渡すと画像に幅と高さを付けて出力する
+    pub fn dimensions(&self) -> HashMap<String, (u32, u32)> {
+        self.downloaded
+            .iter()
+            .filter_map(|downloaded| Some((downloaded.asset.url.clone(), downloaded.dimensions?)))
+            .collect()
+    }
+}
+
+/// `collect_assets` で集めたファイルを並行してダウンロードし、ディレクトリに保存する。
+/// HTTP クライアントは 1 つを使い回す
+pub struct AssetDownloader {
+    client: reqwest::Client,
+    dir: PathBuf,
+    concurrency: usize,
+    deduplicate: bool,
+}
+
+impl AssetDownloader {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self::with_client(reqwest::Client::new(), dir)
+    }
+
+    /// プロキシやタイムアウトを設定したクライアントを使う
+    pub fn with_client(client: reqwest::Client, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            client,
+            dir: dir.into(),
+            concurrency: 4,
+            deduplicate: false,
+        }
+    }
+
+    /// 同時にダウンロードする数（既定は 4）
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// 同じ URL のファイルは 1 回だけダウンロードし、内容が同じファイルは最初に保存した
+    /// 1 つを共有する（どちらの参照も同じパスになる）
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// 保存先のファイル名。ブロック ID（ハイフンなし）とリンク先のファイル名をつなげ、
+    /// ファイル名に使えない文字は `_` にする
+    pub fn file_name(asset: &AssetRef) -> String {
+        let name = utils::file_name_from_url(&asset.url).unwrap_or_else(|| "asset".to_string());
+        let name = name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect::<String>();
+        match asset.block_id.replace('-', "").as_str() {
+            "" => name,
+            id => format!("{}-{}", id, name),
+        }
+    }
+
+    /// `assets` を `concurrency` 件まで並行してダウンロードする。
+    /// 保存先のディレクトリを作れなかったときだけエラーを返し、個々の失敗は `failed` に入れる
+    pub async fn download(&self, assets: &[AssetRef]) -> Result<DownloadReport> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+
+        // deduplicate のときは URL ごとに最初の 1 件だけダウンロードする。
+        // sources[i] は assets[i] の結果が入る targets の位置
+        let mut targets = Vec::new();
+        let mut sources = Vec::with_capacity(assets.len());
+        let mut first_by_url = HashMap::new();
+        for asset in assets {
+            let index = match first_by_url.get(asset.url.as_str()) {
+                Some(&index) if self.deduplicate => index,
+                _ => {
+                    targets.push(asset);
+                    first_by_url.insert(asset.url.as_str(), targets.len() - 1);
+                    targets.len() - 1
+                }
+            };
+            sources.push(index);
+        }
+
+        // 内容のハッシュ → そのハッシュで保存したパス
+        let written = Mutex::new(HashMap::new());
+        // buffered は投入順に結果を返すので、report の並びは assets と同じになる
+        let mut results = stream::iter(&targets)
+            .map(|asset| self.download_one(asset, &written))
+            .buffered(self.concurrency.max(1))
+            .map(Some)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut report = DownloadReport::default();
+        for (asset, &source) in assets.iter().zip(&sources) {
+            // 同じ URL の 2 件目以降は、最初の結果（エラーなら文面）を使う
+            let result = match results[source].take().expect("every target has a result") {
+                Ok(saved) => {
+                    results[source] = Some(Ok(saved.clone()));
+                    Ok(saved)
+                }
+                Err(e) => {
+                    results[source] = Some(Err(anyhow::anyhow!("{:#}", e)));
+                    Err(e)
+                }
+            };
+            match result {
+                Ok((path, dimensions)) => report.downloaded.push(DownloadedAsset {
+                    asset: asset.clone(),
+                    path,
+                    dimensions,
+                }),
+                Err(e) => {
+                    log::warn!("Failed to download {}: {}", asset.url, e);
+                    report.failed.push((asset.clone(), e));
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    // 保存したパスと、画像なら幅と高さを返す
+    async fn download_one(
+        &self,
+        asset: &AssetRef,
+        written: &Mutex<HashMap<u64, Vec<PathBuf>>>,
+    ) -> Result<(PathBuf, Option<(u32, u32)>)> {
+        let response = self
+            .client
+            .get(&asset.url)
+            .send()
+            .await?
+            .error_for_status()?;
+        let bytes = response.bytes().await?;
+        let dimensions = match asset.kind {
+            AssetKind::Image => utils::image_dimensions(&bytes),
+            _ => None,
+        };
+        let path = self.dir.join(Self::file_name(asset));
+        let hash = self.deduplicate.then(|| {
+            let mut hasher = DefaultHasher::new();
+            bytes.hash(&mut hasher);
+            hasher.finish()
+        });
+        if let Some(hash) = hash {
+            let candidates = written
+                .lock()
+                .unwrap()
+                .get(&hash)
+                .cloned()
+                .unwrap_or_default();
+            // ハッシュが一致しても内容が同じとは限らないので、保存済みのファイルと中身を比べる
+            for existing in candidates {
+                if tokio::fs::read(&existing).await.ok().as_deref() == Some(&bytes[..]) {
+                    return Ok((existing, dimensions));
+                }
+            }
+        }
+        tokio::fs::write(&path, &bytes)
+            .await
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        // 書き込みに成功したファイルだけを共有の対象にする
+        if let Some(hash) = hash {
+            written
+                .lock()
+                .unwrap()
+                .entry(hash)
+                .or_default()
+                .push(path.clone());
+        }
+        Ok((path, dimensions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_combines_block_id_and_url_name() {
+        let asset = |block_id: &str, url: &str| AssetRef {
+            block_id: block_id.to_string(),
+            kind: AssetKind::Image,
+            url: url.to_string(),
+        };
+        assert_eq!(
+            AssetDownloader::file_name(&asset(
+                "0123-abcd",
+                "https://example.com/img/photo.png?X-Amz=1"
+            )),
+            "0123abcd-photo.png"
+        );
+        assert_eq!(
+            AssetDownloader::file_name(&asset("", "https://example.com/my%20file (1).pdf")),
+            "my_20file__1_.pdf"
+        );
+        assert_eq!(
+            AssetDownloader::file_name(&asset("b", "https://example.com/")),
+            "b-asset"
+        );
+    }
+}
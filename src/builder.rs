@@ -1,9 +1,26 @@
-use crate::{converters::Converters, notion_to_md::NotionToMarkdown};
+use crate::{
+    client::NotionClientBuilder,
+    config::{
+        AnnotationKind, AssetPathStyle, BareLinkStyle, CalloutStyle, CodeFence, Config, DateFormat,
+        DividerSpacing, FrontmatterConfig, HeadingAnchor, HtmlMode, ImageDefaultAlt, LineEnding,
+        ListIndent, ListKind, ListMarker, OutputFormat, PageCover, PageLinkResolver,
+        PropertyOutput, QuoteStyle, TableAlignment, TemplateMentionStyle, ToggleStyle, UrlRewriter,
+        WideTableStyle,
+    },
+    converters::Converters,
+    notion_to_md::NotionToMarkdown,
+    property::MultiSelectStyle,
+    source::BlockSource,
+};
+use anyhow::Result;
 use notion_client::endpoints::Client;
+use std::{sync::Arc, time::Duration};
 
 pub struct NotionToMarkdownBuilder {
     client: Client,
     pub converters: Converters,
+    pub config: Config,
+    block_source: Option<Arc<dyn BlockSource>>,
 }
 
 impl NotionToMarkdownBuilder {
@@ -11,10 +28,395 @@ impl NotionToMarkdownBuilder {
         Self {
             client,
             converters: Converters::default(),
+            config: Config::default(),
+            block_source: None,
         }
     }
 
+    /// トークンから Notion API クライアントを作り、`config` を初期設定にしたビルダーを返す
+    pub fn from_token(token: &str, config: Config) -> Result<Self> {
+        Self::from_client_builder(NotionClientBuilder::new(token), config)
+    }
+
+    /// タイムアウトや User-Agent を指定した `NotionClientBuilder` からクライアントを作り、
+    /// `config` を初期設定にしたビルダーを返す
+    pub fn from_client_builder(client: NotionClientBuilder, config: Config) -> Result<Self> {
+        Ok(Self {
+            config,
+            ..Self::new(client.build()?)
+        })
+    }
+
+    pub fn output_format(mut self, format: OutputFormat) -> Self {
+        self.config.output_format = format;
+        self
+    }
+
+    pub fn escape_html(mut self, escape: bool) -> Self {
+        self.config.escape_html = escape;
+        self
+    }
+
+    pub fn divider_spacing(mut self, spacing: DividerSpacing) -> Self {
+        self.config.divider_spacing = spacing;
+        self
+    }
+
+    pub fn include_comments(mut self, include: bool) -> Self {
+        self.config.include_comments = include;
+        self
+    }
+
+    pub fn frontmatter(mut self, frontmatter: FrontmatterConfig) -> Self {
+        self.config.frontmatter = Some(frontmatter);
+        self
+    }
+
+    /// プロパティ名と frontmatter のキーの対応を追加する（frontmatter も有効になる）
+    pub fn frontmatter_key(mut self, property: &str, key: &str) -> Self {
+        self.config
+            .frontmatter
+            .get_or_insert_with(FrontmatterConfig::default)
+            .keys
+            .insert(property.to_string(), key.to_string());
+        self
+    }
+
+    /// ページの作成・更新日時を frontmatter の `date:` / `lastmod:` にする（frontmatter も有効になる）
+    pub fn frontmatter_dates(mut self, dates: bool) -> Self {
+        self.config
+            .frontmatter
+            .get_or_insert_with(FrontmatterConfig::default)
+            .dates = dates;
+        self
+    }
+
+    pub fn frontmatter_path(mut self, path: bool) -> Self {
+        self.config
+            .frontmatter
+            .get_or_insert_with(FrontmatterConfig::default)
+            .path = path;
+        self
+    }
+
+    pub fn property_output(mut self, output: PropertyOutput) -> Self {
+        self.config.property_output = output;
+        self
+    }
+
+    pub fn multi_select_style(mut self, style: MultiSelectStyle) -> Self {
+        self.config.multi_select_style = style;
+        self
+    }
+
+    pub fn page_cover(mut self, cover: PageCover) -> Self {
+        self.config.page_cover = cover;
+        self
+    }
+
+    pub fn wrap_width(mut self, width: usize) -> Self {
+        self.config.wrap_width = Some(width);
+        self
+    }
+
+    pub fn line_ending(mut self, ending: LineEnding) -> Self {
+        self.config.line_ending = ending;
+        self
+    }
+
+    pub fn template_mention(mut self, style: TemplateMentionStyle) -> Self {
+        self.config.template_mention = style;
+        self
+    }
+
+    pub fn date_format(mut self, format: DateFormat) -> Self {
+        self.config.date_format = Some(format);
+        self
+    }
+
+    pub fn fetch_concurrency(mut self, concurrency: usize) -> Self {
+        self.config.fetch_concurrency = concurrency;
+        self
+    }
+
+    pub fn strict_expiring_urls(mut self, strict: bool) -> Self {
+        self.config.strict_expiring_urls = strict;
+        self
+    }
+
+    pub fn render_underline(mut self, render: bool) -> Self {
+        self.config.render_underline = render;
+        self
+    }
+
+    pub fn render_color(mut self, render: bool) -> Self {
+        self.config.render_color = render;
+        self
+    }
+
+    pub fn html_mode(mut self, mode: HtmlMode) -> Self {
+        self.config.html_mode = mode;
+        self
+    }
+
+    /// 装飾を内側から包む順番（例: `[Strikethrough, Bold]` なら `~~**text**~~` ではなく
+    /// `**~~text~~**`）
+    pub fn annotation_order(mut self, order: Vec<AnnotationKind>) -> Self {
+        self.config.annotation_order = order;
+        self
+    }
+
+    pub fn code_fence(mut self, fence: CodeFence) -> Self {
+        self.config.code_fence = fence;
+        self
+    }
+
+    /// コードブロックの言語 `notion`（例: `"c++"`）をフェンスでは `name`（例: `"cpp"`）にする
+    pub fn code_language_alias(mut self, notion: &str, name: &str) -> Self {
+        self.config
+            .code_language_aliases
+            .insert(notion.to_string(), name.to_string());
+        self
+    }
+
+    pub fn omit_plaintext_language(mut self, omit: bool) -> Self {
+        self.config.omit_plaintext_language = omit;
+        self
+    }
+
+    pub fn bookmark_as_card(mut self, as_card: bool) -> Self {
+        self.config.bookmark_as_card = as_card;
+        self
+    }
+
+    pub fn bare_link_style(mut self, style: BareLinkStyle) -> Self {
+        self.config.bare_link_style = style;
+        self
+    }
+
+    pub fn link_preview_titles(mut self, fetch: bool) -> Self {
+        self.config.link_preview_titles = fetch;
+        self
+    }
+
+    pub fn resolve_relation_titles(mut self, resolve: bool) -> Self {
+        self.config.resolve_relation_titles = resolve;
+        self
+    }
+
+    pub fn file_sizes(mut self, fetch: bool) -> Self {
+        self.config.file_sizes = fetch;
+        self
+    }
+
+    pub fn link_mention_descriptions(mut self, descriptions: bool) -> Self {
+        self.config.link_mention_descriptions = descriptions;
+        self
+    }
+
+    pub fn database_view_comments(mut self, comments: bool) -> Self {
+        self.config.database_view_comments = comments;
+        self
+    }
+
+    pub fn image_default_alt(mut self, alt: ImageDefaultAlt) -> Self {
+        self.config.image_default_alt = alt;
+        self
+    }
+
+    pub fn image_references(mut self, references: bool) -> Self {
+        self.config.image_references = references;
+        self
+    }
+
+    pub fn image_dimensions(
+        mut self,
+        dimensions: impl IntoIterator<Item = (String, (u32, u32))>,
+    ) -> Self {
+        self.config.image_dimensions.extend(dimensions);
+        self
+    }
+
+    pub fn caption_subtext(mut self, subtext: bool) -> Self {
+        self.config.caption_subtext = subtext;
+        self
+    }
+
+    pub fn repeat_synced_blocks(mut self, repeat: bool) -> Self {
+        self.config.repeat_synced_blocks = repeat;
+        self
+    }
+
+    pub fn inline_templates(mut self, inline: bool) -> Self {
+        self.config.inline_templates = inline;
+        self
+    }
+
+    pub fn table_as_html(mut self, as_html: bool) -> Self {
+        self.config.table_as_html = as_html;
+        self
+    }
+
+    pub fn table_alignment(mut self, alignment: Vec<TableAlignment>) -> Self {
+        self.config.table_alignment = alignment;
+        self
+    }
+
+    pub fn pad_tables(mut self, pad: bool) -> Self {
+        self.config.pad_tables = pad;
+        self
+    }
+
+    pub fn skip_inaccessible(mut self, skip: bool) -> Self {
+        self.config.skip_inaccessible = skip;
+        self
+    }
+
+    pub fn max_table_columns(mut self, columns: usize) -> Self {
+        self.config.max_table_columns = Some(columns);
+        self
+    }
+
+    pub fn wide_tables(mut self, style: WideTableStyle) -> Self {
+        self.config.wide_tables = style;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.config.max_depth = Some(depth);
+        self
+    }
+
+    pub fn iterative_fetch(mut self, iterative: bool) -> Self {
+        self.config.iterative_fetch = iterative;
+        self
+    }
+
+    pub fn max_retries(mut self, retries: usize) -> Self {
+        self.config.max_retries = retries;
+        self
+    }
+
+    pub fn retry_base_delay(mut self, delay: Duration) -> Self {
+        self.config.retry_base_delay = delay;
+        self
+    }
+
+    pub fn unsupported_log_level(mut self, level: Option<log::Level>) -> Self {
+        self.config.unsupported_log_level = level;
+        self
+    }
+
+    pub fn summarize_unsupported(mut self, summarize: bool) -> Self {
+        self.config.summarize_unsupported = summarize;
+        self
+    }
+
+    pub fn loose_lists(mut self, loose: bool) -> Self {
+        self.config.loose_lists = loose;
+        self
+    }
+
+    pub fn two_pass(mut self, two_pass: bool) -> Self {
+        self.config.two_pass = two_pass;
+        self
+    }
+
+    pub fn faithful_spacing(mut self, faithful: bool) -> Self {
+        self.config.faithful_spacing = faithful;
+        self
+    }
+
+    /// `block_type`（`"code"` や `"paragraph"` などコンバータの名前）の変換結果の末尾を
+    /// `count` 個の改行にそろえる
+    pub fn trailing_newlines(mut self, block_type: &str, count: usize) -> Self {
+        self.config
+            .trailing_newlines
+            .insert(block_type.to_string(), count);
+        self
+    }
+
+    pub fn list_indent(mut self, indent: ListIndent) -> Self {
+        self.config.list_indent = indent;
+        self
+    }
+
+    pub fn callout_style(mut self, style: CalloutStyle) -> Self {
+        self.config.callout_style = style;
+        self
+    }
+
+    pub fn toggle_style(mut self, style: ToggleStyle) -> Self {
+        self.config.toggle_style = style;
+        self
+    }
+
+    pub fn empty_toggle_summary(mut self, summary: &str) -> Self {
+        self.config.empty_toggle_summary = summary.to_string();
+        self
+    }
+
+    pub fn quote_style(mut self, style: QuoteStyle) -> Self {
+        self.config.quote_style = style;
+        self
+    }
+
+    pub fn colored_quote_as_callout(mut self, as_callout: bool) -> Self {
+        self.config.colored_quote_as_callout = as_callout;
+        self
+    }
+
+    pub fn heading_anchors(mut self, style: HeadingAnchor) -> Self {
+        self.config.heading_anchors = Some(style);
+        self
+    }
+
+    /// ページ ID → スラッグの対応を登録する。`Some` を返したページへのメンションと子ページは
+    /// `./{slug}.md` への相対リンクになる
+    pub fn page_link<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.config.page_links = Some(PageLinkResolver(Arc::new(f)));
+        self
+    }
+
+    /// リスト項目のマーカーを（種類, 深さ, 番号）から決める関数を登録する。
+    /// `a.` や `iv.` など `-` / `1.` 以外の番号付けに使う
+    pub fn list_marker<F>(mut self, f: F) -> Self
+    where
+        F: Fn(ListKind, usize, usize) -> String + Send + Sync + 'static,
+    {
+        self.config.list_marker = Some(ListMarker(Arc::new(f)));
+        self
+    }
+
+    /// ファイル・画像・動画・カバー画像の URL を出力前に書き換える関数を登録する
+    /// （CDN への置き換え、プロキシの挿入、署名付き URL の再発行など）
+    pub fn rewrite_urls<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.config.url_rewriter = Some(UrlRewriter(Arc::new(f)));
+        self
+    }
+
+    pub fn asset_path_style(mut self, style: AssetPathStyle) -> Self {
+        self.config.asset_path_style = Some(style);
+        self
+    }
+
+    /// ブロックの取得元を差し替える（既定は Notion API クライアント）
+    pub fn block_source<S: BlockSource + 'static>(mut self, source: S) -> Self {
+        self.block_source = Some(Arc::new(source));
+        self
+    }
+
     pub fn build(self) -> NotionToMarkdown {
-        NotionToMarkdown::new(self.client, self.converters)
+        let mut converter = NotionToMarkdown::new(self.client, self.converters, self.config);
+        if let Some(source) = self.block_source {
+            converter.source = source;
+        }
+        converter
     }
 }
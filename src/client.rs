@@ -0,0 +1,100 @@
+use anyhow::Result;
+use notion_client::endpoints::Client;
+use notion_reqwest::ClientBuilder;
+use std::time::Duration;
+
+/// トークンから Notion API クライアントを組み立てる。
+/// タイムアウトや User-Agent など HTTP 周りの設定を指定できる。
+pub struct NotionClientBuilder {
+    token: String,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+}
+
+impl NotionClientBuilder {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+            timeout: None,
+            user_agent: None,
+        }
+    }
+
+    /// リクエスト全体のタイムアウト
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let builder = self.http_client_builder();
+        Ok(Client::new(self.token, Some(builder))?)
+    }
+
+    // notion-client に渡す HTTP クライアントの設定
+    fn http_client_builder(&self) -> ClientBuilder {
+        let mut builder = ClientBuilder::new();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent.clone());
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_client_with_short_timeout() {
+        let client = NotionClientBuilder::new("dummy-token")
+            .timeout(Duration::from_millis(10))
+            .user_agent("notion2md-test")
+            .build();
+        assert!(client.is_ok());
+    }
+
+    // notion-client は API の URL が固定なので、同じ設定の HTTP クライアントで
+    // 応答しないローカルのサーバーにリクエストし、設定が反映されているかを確かめる
+    #[tokio::test]
+    async fn timeout_and_user_agent_reach_the_request() -> Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let (sender, received) = tokio::sync::oneshot::channel();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).await?;
+            let _ = sender.send(String::from_utf8_lossy(&request[..read]).to_lowercase());
+            // 応答せずにクライアントのタイムアウトを待つ
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            anyhow::Ok(())
+        });
+
+        let http = NotionClientBuilder::new("dummy-token")
+            .timeout(Duration::from_millis(200))
+            .user_agent("notion2md-test")
+            .http_client_builder()
+            .build()?;
+        let err = http
+            .get(&url)
+            .send()
+            .await
+            .expect_err("server never responds");
+        assert!(err.is_timeout(), "{}", err);
+        assert!(received.await?.contains("user-agent: notion2md-test"));
+
+        server.abort();
+        Ok(())
+    }
+}
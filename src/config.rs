@@ -0,0 +1,556 @@
+// 変換処理全体に関わる設定値
+
+use crate::property::MultiSelectStyle;
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+
+/// 区切り線（divider）の後ろに入れる空行の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DividerSpacing {
+    /// 常に空行を入れる（`---\n\n`）
+    Loose,
+    /// 空行を入れない（`---\n`）
+    Tight,
+    /// 前後がリスト項目のときだけ空行を省き、番号付きリストの連番も維持する。
+    /// 区切り線でリストは 2 つに分かれる（CommonMark では `<ul>`・`<hr>`・`<ul>` になる）
+    #[default]
+    ContextAware,
+}
+
+/// テンプレートメンション（@Today / @Now）の出力方法
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TemplateMentionStyle {
+    /// 変換時点の日付・日時に置き換える
+    #[default]
+    Resolved,
+    /// 任意の文字列に置き換える（テンプレートエンジンのプレースホルダなど）
+    Token { today: String, now: String },
+}
+
+/// 日付メンションの書式（chrono の strftime 形式）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateFormat {
+    /// 時刻を含まない日付
+    pub date: String,
+    /// 時刻を含む日時。タイムゾーン付きのメンションはそのタイムゾーンで出力する
+    pub datetime: String,
+}
+
+impl Default for DateFormat {
+    fn default() -> Self {
+        Self {
+            date: "%Y-%m-%d".to_string(),
+            datetime: "%Y-%m-%d %H:%M %Z".to_string(),
+        }
+    }
+}
+
+/// ブックマーク・リンクプレビューなど、URL だけのリンクの出力方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BareLinkStyle {
+    /// `[url](url)`
+    #[default]
+    Inline,
+    /// GFM の自動リンク `<url>`。MDX では `<` が JSX になるため `Inline` と同じ出力になる
+    Autolink,
+}
+
+/// リッチテキストの装飾の種類。`Config::annotation_order` で入れ子の順番を決める
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationKind {
+    /// `**text**`
+    Bold,
+    /// `*text*`
+    Italic,
+    /// `~~text~~`
+    Strikethrough,
+    /// `` `text` ``
+    Code,
+    /// `<u>text</u>`（render_underline のときだけ）
+    Underline,
+    /// `<span style>`（render_color のときだけ）
+    Color,
+}
+
+impl AnnotationKind {
+    /// 既定の入れ子の順番（内側から）
+    pub const DEFAULT_ORDER: [AnnotationKind; 6] = [
+        AnnotationKind::Bold,
+        AnnotationKind::Italic,
+        AnnotationKind::Strikethrough,
+        AnnotationKind::Code,
+        AnnotationKind::Underline,
+        AnnotationKind::Color,
+    ];
+}
+
+/// `max_table_columns` を超える列数のテーブルの扱い。どれも警告は出す
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WideTableStyle {
+    /// 先頭の列だけを残し、表の下に省略した旨の注記を置く
+    #[default]
+    Truncate,
+    /// 行と列を入れ替える（列見出しと行見出しも入れ替わる）
+    Transpose,
+    /// 警告だけ出してそのまま出力する
+    Keep,
+}
+
+/// テーブルの列の揃え方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableAlignment {
+    /// 指定なし（`---`）
+    #[default]
+    None,
+    /// 左揃え（`:---`）
+    Left,
+    /// 中央揃え（`:---:`）
+    Center,
+    /// 右揃え（`---:`）
+    Right,
+}
+
+/// リスト項目の子ブロックのインデント
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListIndent {
+    /// 常に指定した数のスペース
+    Spaces(usize),
+    /// 親のマーカーの幅にそろえる（`- ` なら 2、`1. ` なら 3、`10. ` なら 4）。
+    /// CommonMark では番号付きリストの子はこの幅がないと子として扱われない
+    MarkerWidth,
+}
+
+impl Default for ListIndent {
+    fn default() -> Self {
+        ListIndent::Spaces(2)
+    }
+}
+
+/// コードブロックのフェンスに使う文字
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CodeFence {
+    /// バッククォート（`` ` ``）
+    #[default]
+    Backtick,
+    /// チルダ（`~`）
+    Tilde,
+}
+
+impl CodeFence {
+    pub fn as_char(&self) -> char {
+        match self {
+            CodeFence::Backtick => '`',
+            CodeFence::Tilde => '~',
+        }
+    }
+}
+
+/// コールアウトの出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CalloutStyle {
+    /// `> [!note]` 形式の引用ブロック
+    #[default]
+    Blockquote,
+    /// Pandoc の fenced div（`::: note` ... `:::`）。クラスはアイコンの絵文字から決める
+    PandocDiv,
+}
+
+/// 引用ブロックの出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuoteStyle {
+    /// `>` の引用
+    #[default]
+    Blockquote,
+    /// `[!quote]` のコールアウト。書き方は `callout_style` に従う（MDX では `<Callout type="quote">`）
+    Callout,
+}
+
+/// HTML になる出力（埋め込みの `<iframe>`、`<details>` のトグル、下線の `<u>`、色の `<span>`
+/// など）の扱い。HTML を表示しない・取り除く Markdown 環境では `Escaped` か `Stripped` にする
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HtmlMode {
+    /// HTML をそのまま出力する
+    #[default]
+    Inline,
+    /// HTML として解釈させない。埋め込みは言語が `html` のコードブロック、下線や色のタグは
+    /// `&lt;u>` のようにエスケープする。中身が Markdown の `<details>` のトグルと色付きの
+    /// コールアウトの `<div>` は HTML を使わない書き方にする
+    Escaped,
+    /// HTML を出力しない。埋め込みはリンク、下線と色は付けず、トグルは箇条書きにする
+    Stripped,
+}
+
+/// トグルブロックの出力形式（MDX では常に `<Toggle>` コンポーネント）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToggleStyle {
+    /// 見出しを箇条書きにし、中身をその下にインデントする
+    #[default]
+    Bullet,
+    /// `<details>` / `<summary>` で折りたためるようにする
+    Details,
+}
+
+/// キャプションのない画像の代替テキスト
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ImageDefaultAlt {
+    /// 空のまま（`![](...)`）
+    #[default]
+    Empty,
+    /// URL のパスの末尾のファイル名（`![photo.jpg](...)`）
+    FileName,
+    /// 固定の文字列
+    Text(String),
+}
+
+/// 見出しに付けるアンカーの形式。ID は見出しのテキストから `utils::slugify` で作る
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadingAnchor {
+    /// Pandoc の属性（`## Title {#title}`）
+    Pandoc,
+    /// 見出しの直前に置く `<a name="title"></a>`
+    Html,
+}
+
+/// 出力する文書の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Markdown,
+    /// コールアウト・トグル・埋め込みを JSX コンポーネント（`<Callout>` など）で出力し、
+    /// テキスト中の `{` `}` `<` をエスケープする
+    Mdx,
+    /// GitHub 向けの Markdown。Markdown と同じだが、GitHub で表示されない記法は書き換える
+    /// （キャプション付きのコードブロックは、キャプションを太字の行にしてコードの前に置く）
+    Gfm,
+    /// 記法を使わないプレーンテキスト（検索用の索引や要約向け）。リッチテキストは装飾やリンクを
+    /// 外した文字列に、見出しは本文だけの行に、箇条書きは `•` に、引用・コールアウト・コードは
+    /// 中身だけにする。画像やテーブルなどは Markdown と同じ
+    PlainText,
+}
+
+/// 出力する文書の改行コード
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    /// Windows のツールで読む文書向けに `\r\n` にする
+    CrLf,
+}
+
+type ResolveFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+/// ページ ID から出力先のスラッグを求める関数（`./{slug}.md` へのリンクになる）
+#[derive(Clone)]
+pub struct PageLinkResolver(pub Arc<ResolveFn>);
+
+impl PageLinkResolver {
+    pub fn resolve(&self, page_id: &str) -> Option<String> {
+        (self.0)(page_id).map(|slug| format!("./{}.md", slug))
+    }
+}
+
+impl fmt::Debug for PageLinkResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PageLinkResolver(..)")
+    }
+}
+
+/// 出力するファイル・画像・動画の URL を書き換える関数（CDN への置き換えなど）
+#[derive(Clone)]
+pub struct UrlRewriter(pub Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl UrlRewriter {
+    pub fn rewrite(&self, url: &str) -> String {
+        (self.0)(url)
+    }
+}
+
+impl fmt::Debug for UrlRewriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UrlRewriter(..)")
+    }
+}
+
+/// リストの種類（`ListMarker` に渡す）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListKind {
+    Bulleted,
+    Numbered,
+}
+
+/// リスト項目のマーカー（`-` や `1.`、末尾の空白は含めない）を、リストの種類・入れ子の深さ
+/// （最上位が 1）・リスト内の番号（1 から）から決める関数。`a.` や `iv.` などの番号付けに使う
+#[derive(Clone)]
+pub struct ListMarker(pub Arc<dyn Fn(ListKind, usize, usize) -> String + Send + Sync>);
+
+impl ListMarker {
+    pub fn marker(&self, kind: ListKind, depth: usize, index: usize) -> String {
+        (self.0)(kind, depth, index)
+    }
+}
+
+impl fmt::Debug for ListMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ListMarker(..)")
+    }
+}
+
+/// `url_rewriter` がローカルのパス（`://` を含まないもの）を返したときの書き出し方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssetPathStyle {
+    /// パスの区切り文字。`/` と `\` はどちらもこの文字に置き換える
+    pub separator: char,
+    /// ファイル名などの空白を `%20` にする（Markdown のリンク先は空白を含められない）
+    pub encode_spaces: bool,
+}
+
+impl Default for AssetPathStyle {
+    fn default() -> Self {
+        Self {
+            separator: '/',
+            encode_spaces: true,
+        }
+    }
+}
+
+/// ページのカバー画像の出力先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageCover {
+    /// 出力しない
+    #[default]
+    Omit,
+    /// 本文の先頭に画像として出力する
+    Image,
+    /// frontmatter の `cover:` に URL を出力する
+    Frontmatter,
+}
+
+/// ページのプロパティの出力先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PropertyOutput {
+    /// `frontmatter` が設定されていれば YAML frontmatter に出力する
+    #[default]
+    Frontmatter,
+    /// 本文の先頭に 2 列（プロパティ・値）のテーブルとして出力する
+    Table,
+    /// 本文の先頭に定義リスト（`名前` / `: 値`）として出力する
+    DefinitionList,
+    /// 出力しない（`frontmatter` が設定されていても無視する）
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrontmatterConfig {
+    /// Notion のプロパティ名 → frontmatter のキー
+    pub keys: HashMap<String, String>,
+    /// ページの作成日時を `date:`、最終更新日時を `lastmod:` として出力する
+    /// （Hugo などの静的サイトジェネレーター向け）
+    pub dates: bool,
+    /// 祖先ページのタイトルを最上位から順に `path:` のシーケンスとして出力する
+    /// （サイトのナビゲーション向け。親をたどるので API 呼び出しが増える）
+    pub path: bool,
+}
+
+impl Default for FrontmatterConfig {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::from([("Tags".to_string(), "tags".to_string())]),
+            dates: false,
+            path: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub output_format: OutputFormat,
+    /// Markdown 出力でテキスト中の `<` を `&lt;` にして、HTML として解釈されないようにする
+    pub escape_html: bool,
+    pub divider_spacing: DividerSpacing,
+    /// ブロックごとにコメントを取得して脚注として出力する（API 呼び出しが増える）
+    pub include_comments: bool,
+    /// 設定するとページのプロパティを YAML frontmatter として先頭に出力する
+    pub frontmatter: Option<FrontmatterConfig>,
+    pub property_output: PropertyOutput,
+    /// テーブル・CSV・定義リストでの multi_select の選択肢のつなぎ方（frontmatter は YAML のリスト）
+    pub multi_select_style: MultiSelectStyle,
+    pub page_cover: PageCover,
+    /// 設定すると本文をこの桁数で折り返す（コードブロック・テーブル・リンクは分割しない）
+    pub wrap_width: Option<usize>,
+    /// 変換した文書の改行コード。変換の最後にまとめて置き換える
+    pub line_ending: LineEnding,
+    pub template_mention: TemplateMentionStyle,
+    /// 設定すると日付メンションをこの書式で出力する（既定では Notion の plain_text のまま）
+    pub date_format: Option<DateFormat>,
+    /// 子ブロックを並行して取得する数（1 なら逐次取得）
+    pub fetch_concurrency: usize,
+    /// 失効する Notion ホストの URL をそのまま出力しようとしたらエラーにする
+    pub strict_expiring_urls: bool,
+    /// 下線を `<u>` タグで出力する
+    pub render_underline: bool,
+    /// 文字色・背景色を `<span style>` で出力する
+    pub render_color: bool,
+    /// 埋め込み・`<details>` のトグル・下線・色など、HTML になる出力の扱い
+    pub html_mode: HtmlMode,
+    /// 装飾を内側から包む順番。含まれていない種類は後ろに既定の順番で続く
+    pub annotation_order: Vec<AnnotationKind>,
+    /// フェンスの長さは内容に含まれる同じ文字の連続より 1 つ長くなる
+    pub code_fence: CodeFence,
+    /// テーブルを Markdown ではなく HTML の `<table>` で出力する
+    pub table_as_html: bool,
+    /// Markdown のテーブルの列ごとの揃え方（左の列から順に）。足りない列は指定なし
+    pub table_alignment: Vec<TableAlignment>,
+    /// Markdown のテーブルのセルを空白で埋めて列の幅をそろえる
+    pub pad_tables: bool,
+    /// 設定するとこの列数を超えるテーブルで警告を出し、`wide_tables` に従って出力する
+    pub max_table_columns: Option<usize>,
+    pub wide_tables: WideTableStyle,
+    /// 権限のない子ブロックで失敗せず、警告を出してプレースホルダに置き換える
+    pub skip_inaccessible: bool,
+    /// 取得するブロックの最大階層（ページ直下が 1）。超えた分は警告を出して取得しない
+    pub max_depth: Option<usize>,
+    /// ブロックツリーを再帰ではなく作業キューで取得する。再帰版は階層ごとに future を
+    /// 入れ子にして poll するため、数百階層を超える深いツリーではスタックが溢れうる。
+    /// 作業キュー版は階層の深さにかかわらずスタックを消費しない。変換は子ブロックごとに再帰するので、
+    /// 2 MiB のスタックで変換できる入れ子はデバッグビルドで約 600 階層まで（README の「深いブロックツリー」）
+    pub iterative_fetch: bool,
+    /// ページメンションと子ページを相対リンクにする
+    pub page_links: Option<PageLinkResolver>,
+    /// ファイル・画像・動画・カバー画像の URL を出力前に書き換える
+    pub url_rewriter: Option<UrlRewriter>,
+    /// 設定すると `url_rewriter` が返したローカルのパスをこの形式にそろえる
+    pub asset_path_style: Option<AssetPathStyle>,
+    /// 最上位のリスト項目の間に空行を入れる（loose list）
+    pub loose_lists: bool,
+    /// 変換の前に毎回、文書全体をたどって見出しなどを集める収集パスを通す
+    /// （`ConversionContext::document`）。既定では目次ブロックがあるときだけ通す
+    pub two_pass: bool,
+    /// リストなどの子ブロックの空行を詰めず、コンバータの出力をそのまま使う
+    /// （Prettier などで後から整形する場合向け）
+    pub faithful_spacing: bool,
+    pub list_indent: ListIndent,
+    /// 設定すると箇条書き・番号付きリストのマーカーをこの関数で決める
+    pub list_marker: Option<ListMarker>,
+    /// ブロックの種類（`"code"` や `"table"` などコンバータの名前）→ 変換結果の末尾の改行の数。
+    /// 登録のない種類はコンバータの出力のまま
+    pub trailing_newlines: HashMap<String, usize>,
+    pub callout_style: CalloutStyle,
+    pub toggle_style: ToggleStyle,
+    /// `ToggleStyle::Details` で見出しが空のトグルの `<summary>` に入れる文字列（既定は空）
+    pub empty_toggle_summary: String,
+    pub quote_style: QuoteStyle,
+    /// 色の付いた引用ブロックを `callout_style` のコールアウトとして出力する
+    pub colored_quote_as_callout: bool,
+    pub heading_anchors: Option<HeadingAnchor>,
+    /// コードブロックの言語名の置き換え。キーは Notion での名前（`c++`, `plain text` など）か
+    /// 既定の出力（`cplusplus` など）、値はフェンスに付ける名前
+    pub code_language_aliases: HashMap<String, String>,
+    /// 言語が plaintext のコードブロックはフェンスに言語名を付けない
+    pub omit_plaintext_language: bool,
+    /// ブックマークのリンク先から Open Graph 情報を取得し、HTML のリンクカードとして出力する。
+    /// 取得に失敗したブックマークは通常のリンクになる
+    pub bookmark_as_card: bool,
+    pub bare_link_style: BareLinkStyle,
+    /// リンクプレビューのリンク先から Open Graph のタイトルを取得し、リンクの文字列にする。
+    /// 取得に失敗したものは URL のまま
+    pub link_preview_titles: bool,
+    /// データベースの行・本文のプロパティで、relation の関連ページのタイトルを取得して
+    /// リンクの文字列にする（既定ではページ ID）
+    pub resolve_relation_titles: bool,
+    /// file ブロックのファイルのサイズを取得し、リンクの文字列に `(1.2 MB)` のように添える。
+    /// 取得できなかったものは名前だけ
+    pub file_sizes: bool,
+    /// リンクメンションの説明文をリンクの後ろに ` — 説明` として添える
+    pub link_mention_descriptions: bool,
+    /// child_database のビューのフィルタ・並べ替えをコメントとして出力する
+    /// （`BlockSource::retrieve_database_view` が返したときだけ）
+    pub database_view_comments: bool,
+    pub image_default_alt: ImageDefaultAlt,
+    /// 画像を参照形式（`![alt][image-1]`）で出力し、URL の定義を文書の最後にまとめる
+    pub image_references: bool,
+    /// 画像の幅と高さ（元の URL → (幅, 高さ)）。`AssetDownloader` でダウンロードしたときの
+    /// `DownloadReport::dimensions` を渡す。HTML を使えるときは `<img width height>`、
+    /// それ以外は画像のタイトル（`![](url "640x480")`）にする
+    pub image_dimensions: HashMap<String, (u32, u32)>,
+    /// ブックマークとファイルのキャプションを `*キャプション*` の段落としてブロックの下に出力する
+    pub caption_subtext: bool,
+    /// 同じ同期元の同期ブロックが 1 ページに複数回現れたとき、2 回目以降も出力する
+    pub repeat_synced_blocks: bool,
+    /// テンプレートボタンのラベルの後に、テンプレートの中身のブロックも出力する
+    pub inline_templates: bool,
+    /// レート制限（429）で失敗した API 呼び出しを再試行する回数
+    pub max_retries: usize,
+    /// 再試行までの待ち時間。再試行のたびに 2 倍になる
+    pub retry_base_delay: Duration,
+    /// 未対応のブロックを記録するログレベル。`None` なら記録しない
+    pub unsupported_log_level: Option<log::Level>,
+    /// 未対応のブロックを 1 件ずつではなく、種類ごとの件数として変換の最後にまとめて記録する
+    pub summarize_unsupported: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_format: OutputFormat::default(),
+            escape_html: false,
+            divider_spacing: DividerSpacing::default(),
+            include_comments: false,
+            frontmatter: None,
+            property_output: PropertyOutput::default(),
+            multi_select_style: MultiSelectStyle::default(),
+            page_cover: PageCover::default(),
+            wrap_width: None,
+            line_ending: LineEnding::default(),
+            template_mention: TemplateMentionStyle::default(),
+            date_format: None,
+            fetch_concurrency: 1,
+            strict_expiring_urls: false,
+            render_underline: false,
+            render_color: false,
+            html_mode: HtmlMode::default(),
+            annotation_order: AnnotationKind::DEFAULT_ORDER.to_vec(),
+            code_fence: CodeFence::default(),
+            table_as_html: false,
+            table_alignment: Vec::new(),
+            pad_tables: false,
+            max_table_columns: None,
+            wide_tables: WideTableStyle::default(),
+            skip_inaccessible: false,
+            max_depth: None,
+            iterative_fetch: false,
+            page_links: None,
+            url_rewriter: None,
+            asset_path_style: None,
+            loose_lists: false,
+            two_pass: false,
+            faithful_spacing: false,
+            list_indent: ListIndent::default(),
+            list_marker: None,
+            trailing_newlines: HashMap::new(),
+            callout_style: CalloutStyle::default(),
+            quote_style: QuoteStyle::default(),
+            toggle_style: ToggleStyle::default(),
+            empty_toggle_summary: String::new(),
+            colored_quote_as_callout: false,
+            heading_anchors: None,
+            code_language_aliases: HashMap::new(),
+            omit_plaintext_language: false,
+            bookmark_as_card: false,
+            bare_link_style: BareLinkStyle::default(),
+            link_preview_titles: false,
+            resolve_relation_titles: false,
+            file_sizes: false,
+            link_mention_descriptions: false,
+            database_view_comments: false,
+            image_default_alt: ImageDefaultAlt::default(),
+            image_references: false,
+            image_dimensions: HashMap::new(),
+            caption_subtext: false,
+            repeat_synced_blocks: false,
+            inline_templates: false,
+            max_retries: 3,
+            retry_base_delay: Duration::from_secs(1),
+            unsupported_log_level: Some(log::Level::Warn),
+            summarize_unsupported: false,
+        }
+    }
+}
@@ -1,5 +1,5 @@
-use crate::notion_to_md::{BlockWithChildren, ListContext, NotionToMarkdown};
-use notion_client::objects::block::*;
+use crate::notion_to_md::{BlockWithChildren, ConversionContext, ListContext, NotionToMarkdown};
+use notion_client::objects::{block::*, parent::Parent};
 
 // 可読性向上用。Result は anyhow::Result でも独自型でも可。
 type ConvResult = anyhow::Result<String>;
@@ -7,239 +7,855 @@ type ConvResult = anyhow::Result<String>;
 // 各コンバータに渡す共通のペイロード
 pub struct ConvFuncPayload<'a, T> {
     pub value: &'a T,
+    // value を含むブロック本体（ID などを参照したいとき用）
+    pub block: &'a Block,
     pub children: &'a [BlockWithChildren],
+    // 同じ階層で直前・直後にあるブロック
+    pub prev: Option<&'a BlockWithChildren>,
+    pub next: Option<&'a BlockWithChildren>,
     pub list_ctx: &'a mut ListContext,
+    // 親ブロックの種類と入れ子の深さ
+    pub context: ConvContext,
+    // 変換中の文書の状態（見出しのスラッグ、出力済みの同期ブロックなど）
+    pub conversion: &'a ConversionContext,
     pub owner: &'a NotionToMarkdown,
 }
 
+impl<T> ConvFuncPayload<'_, T> {
+    // 子ブロックを同じ文書の一部として変換する
+    pub fn convert_children(&self) -> ConvResult {
+        self.owner
+            .convert_blocks_at_depth(self.children, 1, self.conversion)
+    }
+}
+
+// 変換中のブロックを囲む文脈。リストの中か最上位かで空行を変えたいときなどに使う
+#[derive(Debug, Clone, Default)]
+pub struct ConvContext {
+    // 子ブロックとして変換しているときの親ブロックの種類（最上位なら None）
+    pub parent: Option<BlockType>,
+    // ブロックの入れ子の深さ（最上位が 0）
+    pub depth: usize,
+}
+
 // 共通クロージャ型（ジェネリック T に実際のブロック構造体を入れる）
 type ConvFn<T> = dyn for<'a> Fn(ConvFuncPayload<'a, T>) -> ConvResult + Send + Sync;
 
+impl NotionToMarkdown {
+    // ペイロードを組み立ててコンバータを呼ぶ
+    fn run_converter<T>(
+        &self,
+        convert: &ConvFn<T>,
+        value: &T,
+        bwc: &BlockWithChildren,
+        [prev, next]: [Option<&BlockWithChildren>; 2],
+        list_ctx: &mut ListContext,
+        conversion: &ConversionContext,
+    ) -> ConvResult {
+        let context = conversion.conv_context();
+        // 子ブロックの変換中は、このブロックが親になる
+        let has_children = !bwc.children.is_empty();
+        if has_children {
+            conversion.push_parent(&bwc.block.block_type);
+        }
+        let result = convert(ConvFuncPayload {
+            value,
+            block: &bwc.block,
+            children: &bwc.children,
+            prev,
+            next,
+            list_ctx,
+            context,
+            conversion,
+            owner: self,
+        });
+        if has_children {
+            conversion.pop_parent();
+        }
+        result
+    }
+}
+
 mod default_conv {
-    use notion_client::objects::block::*;
+    use notion_client::objects::{block::*, parent::Parent, rich_text::RichText};
 
     use super::ConvFuncPayload;
-    use crate::{notion_to_md::NotionToMarkdown, utils};
+    use crate::{
+        config::{
+            BareLinkStyle, CalloutStyle, DividerSpacing, HeadingAnchor, HtmlMode, ImageDefaultAlt,
+            ListIndent, ListKind, OutputFormat, QuoteStyle, ToggleStyle, WideTableStyle,
+        },
+        notion_to_md::{is_list_item, NotionToMarkdown},
+        utils,
+    };
 
-    pub fn paragraph(payload: ConvFuncPayload<'_, ParagraphValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        if text.trim().is_empty() {
-            Ok(String::from("\n"))
+    // 子ブロックを変換し、リストの子要素としてインデントする。インデントの幅は list_indent に従い、
+    // ListIndent::MarkerWidth なら親のマーカー（`- ` や `10. `）の幅 marker_width にそろえる。
+    // 変換中は list_ctx を 1 段深くするので、子のコンバータは depth() で入れ子の深さを知れる
+    fn indented_children<T>(
+        payload: &mut ConvFuncPayload<'_, T>,
+        marker_width: usize,
+    ) -> anyhow::Result<String> {
+        if payload.children.is_empty() {
+            return Ok(String::new());
+        }
+
+        payload.list_ctx.push();
+        let child_content = payload.owner.convert_blocks_at_depth(
+            payload.children,
+            payload.list_ctx.depth(),
+            payload.conversion,
+        );
+        payload.list_ctx.pop();
+        Ok(indent_children(
+            payload.owner,
+            &child_content?,
+            marker_width,
+        ))
+    }
+
+    // indented_children の後半。再帰の途中に残るフレームを小さくするため、字下げは別の関数で行う
+    fn indent_children(
+        owner: &NotionToMarkdown,
+        child_content: &str,
+        marker_width: usize,
+    ) -> String {
+        let indent = " ".repeat(match owner.config.list_indent {
+            ListIndent::Spaces(width) => width,
+            ListIndent::MarkerWidth => marker_width,
+        });
+        // faithful_spacing のときは空行を詰めず、空でない行にインデントを付けるだけ
+        if owner.config.faithful_spacing {
+            return child_content
+                .split_inclusive('\n')
+                .map(|line| {
+                    if line.trim().is_empty() {
+                        line.to_string()
+                    } else {
+                        format!("{}{}", indent, line)
+                    }
+                })
+                .collect();
+        }
+        let indented_content = child_content
+            .replace("\n\n", "\n")
+            .lines()
+            .map(|line| format!("{}{}", indent, line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if indented_content.is_empty() {
+            String::new()
         } else {
-            Ok(format!("{}\n", text))
+            format!("{}\n", indented_content)
         }
     }
 
+    pub fn paragraph(mut payload: ConvFuncPayload<'_, ParagraphValue>) -> anyhow::Result<String> {
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
+        let mut content = if text.trim().is_empty() {
+            String::from("\n")
+        } else {
+            format!("{}\n", text)
+        };
+        content.push_str(&indented_children(&mut payload, 2)?);
+        Ok(content)
+    }
+
+    // heading_anchors が設定されていれば見出しにアンカーを付ける
+    fn with_anchor(payload: &ConvFuncPayload<'_, HeadingsValue>, heading: String) -> String {
+        let slug = payload.owner.config.heading_anchors.and_then(|_| {
+            payload
+                .conversion
+                .heading_slug(&NotionToMarkdown::plain_text(&payload.value.rich_text))
+        });
+        match (payload.owner.config.heading_anchors, slug) {
+            (Some(HeadingAnchor::Pandoc), Some(slug)) => format!("{} {{#{}}}\n", heading, slug),
+            (Some(HeadingAnchor::Html), Some(slug)) => {
+                format!("<a name=\"{}\"></a>\n{}\n", slug, heading)
+            }
+            _ => format!("{}\n", heading),
+        }
+    }
+
+    // PlainText では `#` もアンカーも付けず、見出しの文字列だけの行にする
+    fn heading(
+        payload: &ConvFuncPayload<'_, HeadingsValue>,
+        markdown: fn(&str) -> String,
+    ) -> String {
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
+        if is_plain_text(payload.owner) {
+            return format!("{}\n", text);
+        }
+        with_anchor(payload, markdown(&text))
+    }
+
     pub fn heading_1(payload: ConvFuncPayload<'_, HeadingsValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        Ok(format!("{}\n", utils::heading1(&text)))
+        Ok(heading(&payload, utils::heading1))
     }
 
     pub fn heading_2(payload: ConvFuncPayload<'_, HeadingsValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        Ok(format!("{}\n", utils::heading2(&text)))
+        Ok(heading(&payload, utils::heading2))
     }
 
     pub fn heading_3(payload: ConvFuncPayload<'_, HeadingsValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        Ok(format!("{}\n", utils::heading3(&text)))
+        Ok(heading(&payload, utils::heading3))
+    }
+
+    fn is_plain_text(owner: &NotionToMarkdown) -> bool {
+        owner.config.output_format == OutputFormat::PlainText
+    }
+
+    // PlainText の引用・コールアウト: 記号を付けずに本文と子ブロックを並べ、空行で区切る
+    fn plain_block<T>(payload: &ConvFuncPayload<'_, T>, text: &str) -> anyhow::Result<String> {
+        let mut content = format!("{}\n", text);
+        content.push_str(&payload.convert_children()?);
+        content.push('\n');
+        Ok(content)
     }
 
     pub fn bulleted_list_item(
-        payload: ConvFuncPayload<'_, BulletedListItemValue>,
+        mut payload: ConvFuncPayload<'_, BulletedListItemValue>,
     ) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        let mut content = format!("{}\n", utils::bullet(&text, None));
-
-        if !payload.children.is_empty() {
-            let child_content = payload.owner.convert_blocks_to_markdown(payload.children)?;
-            let indented_content = child_content
-                .replace("\n\n", "\n")
-                .lines()
-                .map(|line| format!("  {}", line))
-                .collect::<Vec<_>>()
-                .join("\n");
-            if !indented_content.is_empty() {
-                content.push_str(&format!("{}\n", indented_content));
-            }
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
+        if let Some(list_marker) = &payload.owner.config.list_marker {
+            let index = payload.list_ctx.next_number();
+            let marker = list_marker.marker(ListKind::Bulleted, payload.list_ctx.depth(), index);
+            return custom_list_item(payload, &text, &marker);
+        }
+        if is_plain_text(payload.owner) {
+            return custom_list_item(payload, &text, "•");
         }
+        let mut content = format!("{}\n", utils::bullet(&text, None));
+        content.push_str(&indented_children(&mut payload, 2)?);
+        Ok(content)
+    }
 
+    // list_marker が返したマーカーのリスト項目。子ブロックはマーカーの幅をもとにインデントする
+    fn custom_list_item<T>(
+        mut payload: ConvFuncPayload<'_, T>,
+        text: &str,
+        marker: &str,
+    ) -> anyhow::Result<String> {
+        let mut content = format!("{}\n", utils::list_item(text, marker));
+        let marker_width = marker.chars().count() + 1;
+        content.push_str(&indented_children(&mut payload, marker_width)?);
         Ok(content)
     }
 
     pub fn numbered_list_item(
-        payload: ConvFuncPayload<'_, NumberedListItemValue>,
+        mut payload: ConvFuncPayload<'_, NumberedListItemValue>,
     ) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
         let number = payload.list_ctx.next_number();
-        let mut content = format!("{}\n", utils::bullet(&text, Some(number)));
-
-        if !payload.children.is_empty() {
-            payload.list_ctx.push();
-            let child_content = payload.owner.convert_blocks_to_markdown(payload.children)?;
-            payload.list_ctx.pop();
-
-            let indented_content = child_content
-                .replace("\n\n", "\n")
-                .lines()
-                .map(|line| format!("  {}", line))
-                .collect::<Vec<_>>()
-                .join("\n");
-            if !indented_content.is_empty() {
-                content.push_str(&format!("{}\n", indented_content));
-            }
+        if let Some(list_marker) = &payload.owner.config.list_marker {
+            let marker = list_marker.marker(ListKind::Numbered, payload.list_ctx.depth(), number);
+            return custom_list_item(payload, &text, &marker);
         }
-
+        // 改行を含む本文は番号の桁数に合わせて本文の位置に揃える。
+        // 子ブロックは既定では桁数に関わらず 2 スペース、ListIndent::MarkerWidth なら番号の幅でインデントする
+        let mut content = format!("{}\n", utils::bullet(&text, Some(number)));
+        let marker_width = format!("{}. ", number).len();
+        content.push_str(&indented_children(&mut payload, marker_width)?);
         Ok(content)
     }
 
-    pub fn to_do(payload: ConvFuncPayload<'_, ToDoValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        Ok(format!(
+    pub fn to_do(mut payload: ConvFuncPayload<'_, ToDoValue>) -> anyhow::Result<String> {
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
+        if is_plain_text(payload.owner) {
+            let marker = if payload.value.checked.unwrap_or_default() {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            return custom_list_item(payload, &text, marker);
+        }
+        let mut content = format!(
             "{}\n",
             utils::todo(&text, payload.value.checked.unwrap_or_default())
-        ))
+        );
+        content.push_str(&indented_children(&mut payload, 2)?);
+        Ok(content)
     }
 
-    pub fn toggle(payload: ConvFuncPayload<'_, ToggleValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        let mut content = format!("{}\n", utils::bullet(&text, None));
-
-        if !payload.children.is_empty() {
-            let child_content = payload.owner.convert_blocks_to_markdown(payload.children)?;
-            let indented_content = child_content
-                .replace("\n\n", "\n")
-                .lines()
-                .map(|line| format!("  {}", line))
-                .collect::<Vec<_>>()
-                .join("\n");
-            if !indented_content.is_empty() {
-                content.push_str(&format!("{}\n", indented_content));
-            }
+    pub fn toggle(mut payload: ConvFuncPayload<'_, ToggleValue>) -> anyhow::Result<String> {
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
+        if payload.owner.config.output_format == OutputFormat::Mdx {
+            let body = payload.convert_children()?;
+            let body = match body.trim_end() {
+                "" => String::new(),
+                body => format!("{}\n", body),
+            };
+            // 属性値は JSX の文字列なので、装飾やエスケープのないプレーンテキストを使う
+            let title = NotionToMarkdown::plain_text(&payload.value.rich_text);
+            return Ok(utils::jsx_component("Toggle", &[("title", &title)], &body));
+        }
+        if is_plain_text(payload.owner) {
+            return custom_list_item(payload, &text, "•");
+        }
+        // <details> の中身は Markdown なので、HTML を使えないときは箇条書きにする
+        if payload.owner.config.toggle_style == ToggleStyle::Details
+            && payload.owner.config.html_mode == HtmlMode::Inline
+        {
+            // 見出しが空でも中身は出力するので、<summary> には empty_toggle_summary を入れる
+            let summary = match text.trim() {
+                "" => payload.owner.config.empty_toggle_summary.as_str(),
+                _ => text.as_str(),
+            };
+            let body = payload.convert_children()?;
+            let body = match body.trim_end() {
+                "" => String::new(),
+                body => format!("\n{}\n", body),
+            };
+            return Ok(utils::toggle(Some(summary), Some(&body)));
         }
 
+        let mut content = format!("{}\n", utils::bullet(&text, None));
+        content.push_str(&indented_children(&mut payload, 2)?);
         Ok(content)
     }
 
     pub fn quote(payload: ConvFuncPayload<'_, QuoteValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        let mut content = text
-            .lines()
-            .map(|line| format!("{}\n", utils::quote(line)))
-            .collect::<String>();
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
+        // 色の付いた引用はコールアウトとして出力できる（引用ブロックはアイコンを持たない）
+        if payload.owner.config.colored_quote_as_callout
+            && payload.value.color != TextColor::Default
+        {
+            return render_callout(&payload, &text, None, &payload.value.color);
+        }
+        if payload.owner.config.quote_style == QuoteStyle::Callout {
+            return render_callout_as(&payload, "quote", "quote", &text, &payload.value.color);
+        }
 
-        if !payload.children.is_empty() {
-            let child_content = payload.owner.convert_blocks_to_markdown(payload.children)?;
-            let formatted_content = child_content
-                .lines()
-                .map(utils::quote)
-                .collect::<Vec<_>>()
-                .join("\n");
-            if !formatted_content.is_empty() {
-                content.push_str(&format!("{}\n", formatted_content));
-            }
+        if is_plain_text(payload.owner) {
+            return plain_block(&payload, &text);
         }
+        let child_content = payload.convert_children()?;
+        Ok(utils::blockquote(&text, &child_content))
+    }
 
-        content.push('\n');
+    // テンプレートボタンはラベルだけを出力する。inline_templates なら中身（複製されるブロック）も続ける
+    pub fn template(payload: ConvFuncPayload<'_, TemplateValue>) -> anyhow::Result<String> {
+        let label = payload.owner.convert_rich_text(&payload.value.rich_text);
+        let mut content = if label.trim().is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", label)
+        };
+        if payload.owner.config.inline_templates && !payload.children.is_empty() {
+            content.push('\n');
+            content.push_str(&payload.convert_children()?);
+        }
         Ok(content)
     }
 
     pub fn code(payload: ConvFuncPayload<'_, CodeValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        let language = format!("{:?}", payload.value.language).to_lowercase();
-        Ok(format!("{}\n", utils::code_block(&text, Some(&language))))
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
+        if is_plain_text(payload.owner) {
+            return Ok(format!("{}\n\n", text.trim_end()));
+        }
+        let language = code_language(payload.owner, &payload.value.language);
+        let code = utils::code_block_with_options(
+            &text,
+            Some(&language),
+            payload.owner.config.code_fence.as_char(),
+            payload.owner.config.omit_plaintext_language,
+        );
+        // GitHub はフェンスのタイトルを表示しないので、キャプションを太字の行にして前に置く
+        let caption = payload.owner.convert_rich_text(&payload.value.caption);
+        if payload.owner.config.output_format == OutputFormat::Gfm && !caption.trim().is_empty() {
+            return Ok(format!("**{}**\n\n{}\n", caption.trim(), code));
+        }
+        Ok(format!("{}\n", code))
+    }
+
+    // フェンスに付ける言語名。既定はバリアント名の小文字（`CPlusPlus` → `cplusplus`）で、
+    // code_language_aliases に Notion での名前（`c++`）か既定の名前があればそれを使う
+    fn code_language(owner: &NotionToMarkdown, language: &Language) -> String {
+        let default = format!("{:?}", language).to_lowercase();
+        let notion_name = match language {
+            Language::CPlusPlus => "c++",
+            Language::CSharp => "c#",
+            Language::FSharp => "f#",
+            Language::ObjectiveC => "objective-c",
+            Language::PlainText => "plain text",
+            Language::VbNet => "vb.net",
+            Language::VisualBasic => "visual basic",
+            Language::JavaOrCOrCPlusPlusOrCSharp => "java/c/c++/c#",
+            _ => default.as_str(),
+        };
+        let aliases = &owner.config.code_language_aliases;
+        aliases
+            .get(notion_name)
+            .or_else(|| aliases.get(&default))
+            .cloned()
+            .unwrap_or(default)
     }
 
     pub fn callout(payload: ConvFuncPayload<'_, CalloutValue>) -> anyhow::Result<String> {
-        let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
-        let mut content = format!("> [!note] {}\n", text);
+        let text = payload.owner.convert_rich_text(&payload.value.rich_text);
+        let emoji = match &payload.value.icon {
+            Some(Icon::Emoji(emoji)) => Some(emoji.emoji.as_str()),
+            _ => None,
+        };
+        render_callout(&payload, &text, emoji, &payload.value.color)
+    }
+
+    // callout_style に従ってコールアウトを出力する。色付きの引用からも使う。
+    // render_color のときは色を MDX では color 属性、それ以外では <div> の背景色などにする
+    fn render_callout<T>(
+        payload: &ConvFuncPayload<'_, T>,
+        text: &str,
+        emoji: Option<&str>,
+        color: &TextColor,
+    ) -> anyhow::Result<String> {
+        let class = utils::callout_class(emoji);
+        render_callout_as(payload, class, "note", text, color)
+    }
+
+    // `class` は fenced div のクラスと JSX の type、`label` は `> [!label]` の種類
+    fn render_callout_as<T>(
+        payload: &ConvFuncPayload<'_, T>,
+        class: &str,
+        label: &str,
+        text: &str,
+        color: &TextColor,
+    ) -> anyhow::Result<String> {
+        let owner = payload.owner;
+        if is_plain_text(owner) {
+            return plain_block(payload, text);
+        }
+        let color = Some(utils::block_color(color)).filter(|_| owner.config.render_color);
+        if owner.config.output_format == OutputFormat::Mdx {
+            let body = callout_body(payload, text)?;
+            let color_name = color
+                .as_ref()
+                .filter(|color| **color != notion_client::objects::rich_text::TextColor::Default)
+                .map(utils::color_name);
+            let mut attributes = vec![("type", class)];
+            if let Some(color_name) = &color_name {
+                attributes.push(("color", color_name.as_str()));
+            }
+            return Ok(utils::jsx_component("Callout", &attributes, &body));
+        }
+
+        let callout = if owner.config.callout_style == CalloutStyle::PandocDiv {
+            let body = callout_body(payload, text)?;
+            utils::pandoc_div(class, &body)
+        } else {
+            blockquote_callout(payload, label, text)?
+        };
+        Ok(match color {
+            Some(color) if owner.config.html_mode == HtmlMode::Inline => {
+                utils::color_div(&callout, &color)
+            }
+            _ => callout,
+        })
+    }
+
+    // Obsidian 形式（`> [!note]`）のコールアウト
+    fn blockquote_callout<T>(
+        payload: &ConvFuncPayload<'_, T>,
+        label: &str,
+        text: &str,
+    ) -> anyhow::Result<String> {
+        let child_content = payload
+            .convert_children()?
+            .lines()
+            .filter(|line| text.is_empty() || !line.contains(text))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(utils::blockquote(
+            &format!("[!{}] {}", label, text),
+            &child_content,
+        ))
+    }
 
+    // 本文と子ブロックを空行で区切ってつなげる（fenced div や JSX の中身）
+    fn callout_body<T>(payload: &ConvFuncPayload<'_, T>, text: &str) -> anyhow::Result<String> {
+        let mut body = String::new();
+        if !text.trim().is_empty() {
+            body.push_str(&format!("{}\n", text));
+        }
         if !payload.children.is_empty() {
-            let child_content = payload.owner.convert_blocks_to_markdown(payload.children)?;
-            let formatted_content = child_content
-                .lines()
-                .filter(|line| !line.contains(&text))
-                .map(|line| format!("> {}", line))
-                .collect::<Vec<_>>()
-                .join("\n");
-            if !formatted_content.is_empty() {
-                content.push_str(&format!("{}\n", formatted_content));
+            let child_content = payload.convert_children()?;
+            if !body.is_empty() && !child_content.trim().is_empty() {
+                body.push('\n');
             }
+            body.push_str(child_content.trim_end());
+            body.push('\n');
         }
+        Ok(body)
+    }
 
-        content.push('\n');
-        Ok(content)
+    // 子ブロック（参照なら同期元の内容）をそのまま出力する。
+    // 同じ同期元が 2 回目以降に現れたら、repeat_synced_blocks でなければ何も出力しない
+    pub fn synced_block(payload: ConvFuncPayload<'_, SyncedBlockValue>) -> anyhow::Result<String> {
+        let original = match &payload.value.synced_from {
+            Some(SyncedFrom::BlockId { block_id }) => block_id.as_str(),
+            None => payload.block.id.as_deref().unwrap_or_default(),
+        };
+        if !payload.owner.config.repeat_synced_blocks
+            && !original.is_empty()
+            && !payload.conversion.mark_synced_rendered(original)
+        {
+            return Ok(String::new());
+        }
+        payload.convert_children()
     }
 
     pub fn image(payload: ConvFuncPayload<'_, ImageValue>) -> anyhow::Result<String> {
-        let url = NotionToMarkdown::get_file_url(&payload.value.file_type);
-        Ok(format!("![]({})\n\n", url))
+        let url = payload.owner.file_url(&payload.value.file_type)?;
+        // notion-client の ImageValue はキャプションを持たないので、代替テキストは設定から決める
+        let alt = match &payload.owner.config.image_default_alt {
+            ImageDefaultAlt::Empty => String::new(),
+            ImageDefaultAlt::FileName => utils::file_name_from_url(&url).unwrap_or_default(),
+            ImageDefaultAlt::Text(text) => text.clone(),
+        };
+        if payload.owner.config.image_references {
+            let id = payload.conversion.image_reference(&url);
+            return Ok(format!("![{}][{}]\n\n", alt, id));
+        }
+        // 幅と高さは書き換える前の URL で引く
+        let raw_url = NotionToMarkdown::get_file_url(&payload.value.file_type);
+        match payload.owner.config.image_dimensions.get(&raw_url) {
+            Some((width, height)) if payload.owner.config.html_mode == HtmlMode::Inline => {
+                Ok(format!(
+                    "<img src=\"{}\" alt=\"{}\" width=\"{}\" height=\"{}\">\n\n",
+                    utils::html_escape(&url),
+                    utils::html_escape(&alt),
+                    width,
+                    height
+                ))
+            }
+            Some((width, height)) => {
+                Ok(format!("![{}]({} \"{}x{}\")\n\n", alt, url, width, height))
+            }
+            None => Ok(format!("![{}]({})\n\n", alt, url)),
+        }
     }
 
     pub fn video(payload: ConvFuncPayload<'_, VideoValue>) -> anyhow::Result<String> {
-        let url = NotionToMarkdown::get_file_url(&payload.value.file_type);
+        let url = payload.owner.file_url(&payload.value.file_type)?;
         Ok(format!("![]({})\n\n", url))
     }
 
     pub fn bookmark(payload: ConvFuncPayload<'_, BookmarkValue>) -> anyhow::Result<String> {
+        let link = match payload.owner.link_card_of(&payload.value.url) {
+            Some(metadata) if payload.owner.config.bookmark_as_card => {
+                utils::link_card(&payload.value.url, &metadata)
+            }
+            _ => bare_link(payload.owner, &payload.value.url),
+        };
         Ok(format!(
-            "[{}]({})\n\n",
-            payload.value.url, payload.value.url
+            "{}\n\n{}",
+            link,
+            caption_subtext(payload.owner, &payload.value.caption)
         ))
     }
 
+    // ファイル名（なければ URL の末尾）へのリンク。file_sizes でサイズが分かれば `(1.2 MB)` を添える
+    pub fn file(payload: ConvFuncPayload<'_, FileValue>) -> anyhow::Result<String> {
+        let url = payload.owner.file_url(&payload.value.file_type)?;
+        let raw_url = NotionToMarkdown::get_file_url(&payload.value.file_type);
+        let name = match payload.value.name.trim() {
+            "" => utils::file_name_from_url(&raw_url).unwrap_or_else(|| "file".to_string()),
+            name => name.to_string(),
+        };
+        let mut text = utils::escape_text(&name, &payload.owner.config);
+        if let Some(size) = payload.owner.file_size_of(&raw_url) {
+            text.push_str(&format!(" ({})", utils::format_bytes(size)));
+        }
+        Ok(format!(
+            "{}\n\n{}",
+            utils::link(&text, &url),
+            caption_subtext(payload.owner, &payload.value.caption)
+        ))
+    }
+
+    // caption_subtext のとき、キャプションを斜体の段落にしてブロックの下に置く
+    fn caption_subtext(owner: &NotionToMarkdown, caption: &[RichText]) -> String {
+        let text = owner.convert_rich_text(caption);
+        if !owner.config.caption_subtext || text.trim().is_empty() {
+            return String::new();
+        }
+        format!("{}\n\n", utils::caption(text.trim()))
+    }
+
     pub fn link_preview(payload: ConvFuncPayload<'_, LinkPreviewValue>) -> anyhow::Result<String> {
+        if payload.owner.config.link_preview_titles {
+            if let Some(metadata) = payload.owner.link_card_of(&payload.value.url) {
+                let title = utils::escape_text(&metadata.title, &payload.owner.config)
+                    .replace('[', "\\[")
+                    .replace(']', "\\]");
+                return Ok(format!("{}\n\n", utils::link(&title, &payload.value.url)));
+            }
+        }
         Ok(format!(
-            "[{}]({})\n\n",
-            payload.value.url, payload.value.url
+            "{}\n\n",
+            bare_link(payload.owner, &payload.value.url)
         ))
     }
 
-    pub fn divider(_payload: ConvFuncPayload<'_, DividerValue>) -> anyhow::Result<String> {
-        Ok("---\n\n".to_string())
+    // URL だけのリンク。bare_link_style に従う
+    fn bare_link(owner: &NotionToMarkdown, url: &str) -> String {
+        match (owner.config.bare_link_style, owner.config.output_format) {
+            (BareLinkStyle::Autolink, OutputFormat::Markdown | OutputFormat::Gfm) => {
+                utils::autolink(url)
+            }
+            _ => utils::link(url, url),
+        }
     }
 
-    pub fn table(payload: ConvFuncPayload<'_, TableValue>) -> anyhow::Result<String> {
-        let mut content = String::new();
+    pub fn divider(payload: ConvFuncPayload<'_, DividerValue>) -> anyhow::Result<String> {
+        let blank_line = match payload.owner.config.divider_spacing {
+            DividerSpacing::Loose => true,
+            DividerSpacing::Tight => false,
+            // 空行を省いても区切り線はリストを閉じるので、前後は別々のリストになる
+            DividerSpacing::ContextAware => {
+                !(is_list_item(payload.prev) && is_list_item(payload.next))
+            }
+        };
+        if blank_line {
+            Ok("---\n\n".to_string())
+        } else {
+            Ok("---\n".to_string())
+        }
+    }
 
-        if !payload.children.is_empty() {
-            if let Some(first_row) = payload.children.first() {
-                if let BlockType::TableRow { table_row } = &first_row.block.block_type {
-                    content.push('|');
-                    for cell in &table_row.cells {
-                        let cell_text = NotionToMarkdown::rich_text_to_markdown(cell);
-                        content.push_str(&format!(" {} |", cell_text));
-                    }
-                    content.push('\n');
+    pub fn equation(payload: ConvFuncPayload<'_, EquationValue>) -> anyhow::Result<String> {
+        // 引用の中では各行に `> ` が付くので、`$$` の囲みが崩れないよう 1 行にまとめる
+        if is_quoted(payload.owner, payload.context.parent.as_ref()) {
+            return Ok(format!(
+                "{}\n\n",
+                utils::single_line_equation(&payload.value.expression)
+            ));
+        }
+        Ok(format!(
+            "{}\n\n",
+            utils::equation(&payload.value.expression)
+        ))
+    }
 
-                    content.push('|');
-                    for _ in 0..table_row.cells.len() {
-                        content.push_str(" --- |");
-                    }
-                    content.push('\n');
-
-                    for row in payload.children.iter().skip(1) {
-                        if let BlockType::TableRow { table_row } = &row.block.block_type {
-                            content.push('|');
-                            for cell in &table_row.cells {
-                                let cell_text = NotionToMarkdown::rich_text_to_markdown(cell);
-                                content.push_str(&format!(" {} |", cell_text));
-                            }
-                            content.push('\n');
-                        }
+    // 親ブロックが `> ` で始まる引用として出力されるか
+    fn is_quoted(owner: &NotionToMarkdown, parent: Option<&BlockType>) -> bool {
+        if is_plain_text(owner) {
+            return false;
+        }
+        let callout_quoted = owner.config.output_format != OutputFormat::Mdx
+            && owner.config.callout_style != CalloutStyle::PandocDiv;
+        match parent {
+            Some(BlockType::Callout { .. }) => callout_quoted,
+            Some(BlockType::Quote { quote }) => {
+                let as_callout =
+                    owner.config.colored_quote_as_callout && quote.color != TextColor::Default;
+                !as_callout || callout_quoted
+            }
+            _ => false,
+        }
+    }
+
+    pub fn child_page(payload: ConvFuncPayload<'_, ChildPageValue>) -> anyhow::Result<String> {
+        let id = payload.block.id.as_deref().unwrap_or_default();
+        let href = payload
+            .owner
+            .config
+            .page_links
+            .as_ref()
+            .and_then(|r| r.resolve(id))
+            .unwrap_or_else(|| utils::notion_url(id));
+        Ok(format!("{}\n\n", utils::link(&payload.value.title, &href)))
+    }
+
+    // 祖先のページは取得時に解決済み。解決できなかったときは何も出力しない。
+    // 名前は notion-client のフィールド名（breadcrump）に合わせている
+    pub fn breadcrump(payload: ConvFuncPayload<'_, BreadcrumpValue>) -> anyhow::Result<String> {
+        let id = payload.block.id.as_deref().unwrap_or_default();
+        let ancestry = payload.owner.ancestry_of(id);
+        if ancestry.is_empty() {
+            return Ok(String::new());
+        }
+
+        let trail = ancestry
+            .iter()
+            .map(|(id, title)| {
+                let href = payload
+                    .owner
+                    .config
+                    .page_links
+                    .as_ref()
+                    .and_then(|r| r.resolve(id))
+                    .unwrap_or_else(|| utils::notion_url(id));
+                utils::link(title, &href)
+            })
+            .collect::<Vec<_>>()
+            .join(" / ");
+        Ok(format!("{}\n\n", trail))
+    }
+
+    pub fn child_database(
+        payload: ConvFuncPayload<'_, ChildDatabaseValue>,
+    ) -> anyhow::Result<String> {
+        let id = payload.block.id.as_deref().unwrap_or_default();
+        let link = utils::link(&payload.value.title, &utils::notion_url(id));
+        // database_view_comments のとき、ビューのフィルタ・並べ替えをコメントで残す
+        let view = payload
+            .owner
+            .database_view_of(id)
+            .filter(|view| view.filter.is_some() || !view.sorts.is_empty());
+        let Some(view) = view else {
+            return Ok(format!("{}\n\n", link));
+        };
+        let mut notes = Vec::new();
+        if let Some(filter) = &view.filter {
+            notes.push(format!("filter: {}", filter));
+        }
+        if !view.sorts.is_empty() {
+            notes.push(format!("sort: {}", view.sorts.join(", ")));
+        }
+        let note = format!("notion2md: {}", notes.join("; ").replace("--", "- -"));
+        let comment = match payload.owner.config.output_format {
+            OutputFormat::Markdown | OutputFormat::Gfm => format!("<!-- {} -->", note),
+            OutputFormat::Mdx => format!("{{/* {} */}}", note.replace("*/", "* /")),
+            // プレーンテキストにはコメントの書き方がないので、ビューの設定は出力しない
+            OutputFormat::PlainText => return Ok(format!("{}\n\n", link)),
+        };
+        Ok(format!("{}\n{}\n\n", comment, link))
+    }
+
+    // リンク先のタイトルは取得時に解決済み。解決できなかったときは ID をそのまま使う
+    pub fn link_to_page(payload: ConvFuncPayload<'_, Parent>) -> anyhow::Result<String> {
+        let id = match payload.value {
+            Parent::DatabaseId { database_id } => database_id,
+            Parent::PageId { page_id } => page_id,
+            _ => return Ok(String::new()),
+        };
+        let title = payload.owner.title_of(id).unwrap_or_else(|| id.clone());
+        Ok(format!(
+            "{}\n\n",
+            utils::link(&title, &utils::notion_url(id))
+        ))
+    }
+
+    // 表の各行のセルを変換する。区切り線など行以外の子ブロックは表を壊さないよう読み飛ばす
+    fn table_rows(
+        payload: &ConvFuncPayload<'_, TableValue>,
+        convert_cell: impl Fn(&Vec<RichText>) -> String,
+    ) -> Vec<Vec<String>> {
+        payload
+            .children
+            .iter()
+            .filter_map(|row| match &row.block.block_type {
+                BlockType::TableRow { table_row } => {
+                    Some(table_row.cells.iter().map(&convert_cell).collect())
+                }
+                other => {
+                    log::debug!("Skipping non-row block inside table: {:?}", other);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn table(payload: ConvFuncPayload<'_, TableValue>) -> anyhow::Result<String> {
+        let config = &payload.owner.config;
+        let mut rows = table_rows(&payload, |cell| {
+            let text = payload.owner.convert_rich_text(cell);
+            if config.table_as_html {
+                text
+            } else {
+                utils::table_cell(&text)
+            }
+        });
+
+        // max_table_columns を超える表は警告を出し、wide_tables に従って列を削るか行と列を入れ替える
+        let mut column_header = payload.value.has_column_header;
+        let mut row_header = payload.value.has_row_header;
+        let mut note = String::new();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if let Some(max) = config.max_table_columns.filter(|&max| columns > max) {
+            log::warn!(
+                "Table {} has {} columns, more than the maximum of {}",
+                payload.block.id.as_deref().unwrap_or("(unknown)"),
+                columns,
+                max
+            );
+            match config.wide_tables {
+                WideTableStyle::Truncate => {
+                    for row in &mut rows {
+                        row.truncate(max);
                     }
+                    note = format!(
+                        "{}\n\n",
+                        utils::caption(&format!("Showing {} of {} columns", max, columns))
+                    );
                 }
+                WideTableStyle::Transpose => {
+                    rows = utils::transpose(&rows);
+                    std::mem::swap(&mut column_header, &mut row_header);
+                }
+                WideTableStyle::Keep => {}
             }
         }
+
+        if config.table_as_html {
+            return Ok(format!(
+                "{}\n\n{}",
+                utils::html_table(&rows, column_header, row_header),
+                note
+            ));
+        }
+        if rows.is_empty() {
+            return Ok("\n".to_string());
+        }
+        let rows = rows
+            .iter()
+            .map(|row| row.iter().map(String::as_str).collect())
+            .collect::<Vec<Vec<&str>>>();
+        Ok(format!(
+            "{}\n\n{}",
+            utils::aligned_table(&rows, &config.table_alignment, config.pad_tables),
+            note
+        ))
+    }
+
+    // 文書中の見出しへのリンクを、見出しのレベルに合わせて入れ子の箇条書きにする
+    pub fn table_of_contents(
+        payload: ConvFuncPayload<'_, TableOfContentsValue>,
+    ) -> anyhow::Result<String> {
+        let entries = payload.conversion.toc_entries();
+        let Some(top) = entries.iter().map(|entry| entry.level).min() else {
+            return Ok(String::new());
+        };
+        let mut content = String::new();
+        for entry in entries {
+            let text = utils::escape_text(&entry.text, &payload.owner.config);
+            content.push_str(&format!(
+                "{}- {}\n",
+                "  ".repeat(entry.level - top),
+                utils::link(&text, &format!("#{}", entry.slug))
+            ));
+        }
         content.push('\n');
         Ok(content)
     }
 
     pub fn embed(payload: ConvFuncPayload<'_, EmbedValue>) -> anyhow::Result<String> {
-        Ok(format!(
-            "<iframe src=\"{}\" width=\"100%\" height=\"500px\"></iframe>\n\n",
+        if payload.owner.config.output_format == OutputFormat::Mdx {
+            return Ok(utils::jsx_component(
+                "Embed",
+                &[("src", &payload.value.url)],
+                "",
+            ));
+        }
+        let iframe = format!(
+            "<iframe src=\"{}\" width=\"100%\" height=\"500px\"></iframe>",
             payload.value.url
-        ))
+        );
+        Ok(match payload.owner.config.html_mode {
+            HtmlMode::Inline => format!("{}\n\n", iframe),
+            HtmlMode::Escaped => format!("{}\n\n", utils::code_block(&iframe, Some("html"))),
+            HtmlMode::Stripped => format!("{}\n\n", bare_link(payload.owner, &payload.value.url)),
+        })
     }
 }
 
@@ -278,30 +894,54 @@ macro_rules! define_converters {
         }
 
         // ③ dispatch
+        // match の分岐の中で変換を呼ぶと、デバッグビルドでは分岐の数だけスタックを確保して
+        // 入れ子 1 段あたりの消費が膨らむ。分岐では種類ごとの関数を選ぶだけにして、呼び出しは 1 か所で行う
+        mod dispatch {
+            use super::*;
+
+            pub(super) type DispatchFn = fn(
+                &NotionToMarkdown,
+                &BlockWithChildren,
+                [Option<&BlockWithChildren>; 2],
+                &mut ListContext,
+                &ConversionContext,
+            ) -> ConvResult;
+
+            $(
+            pub(super) fn $field(
+                owner: &NotionToMarkdown,
+                bwc: &BlockWithChildren,
+                siblings: [Option<&BlockWithChildren>; 2],
+                ctx: &mut ListContext,
+                conversion: &ConversionContext,
+            ) -> ConvResult {
+                let BlockType::$Variant { $field: inner } = &bwc.block.block_type else {
+                    unreachable!()
+                };
+                owner
+                    .run_converter(&*owner.converters.$field, inner, bwc, siblings, ctx, conversion)
+                    .map(|markdown| owner.apply_trailing_newlines(stringify!($field), markdown))
+            }
+            )+
+        }
+
         impl NotionToMarkdown {
             pub fn convert_block_to_markdown_inner(
                 &self,
                 bwc: &BlockWithChildren,
+                prev: Option<&BlockWithChildren>,
+                next: Option<&BlockWithChildren>,
                 ctx: &mut ListContext,
+                conversion: &ConversionContext,
             ) -> ConvResult {
-                match &bwc.block.block_type {
-                    $(
-                    BlockType::$Variant { $field: inner } => {
-                        (self.converters.$field)(
-                            ConvFuncPayload {
-                                value: inner,
-                                children: &bwc.children,
-                                list_ctx: ctx,
-                                owner: self,
-                            }
-                        )
-                    }
-                    )+
+                let convert: dispatch::DispatchFn = match &bwc.block.block_type {
+                    $( BlockType::$Variant { .. } => dispatch::$field, )+
                     _ => {
-                        log::warn!("Unsupported block type: {:?}", bwc.block.block_type);
-                        Ok(String::new())
+                        self.report_unsupported(conversion, &bwc.block.block_type);
+                        return Ok(String::new());
                     }
-                }
+                };
+                convert(self, bwc, [prev, next], ctx, conversion)
             }
         }
     };
@@ -324,8 +964,17 @@ define_converters! {
     (Image,         image,            ImageValue),
     (Video,         video,            VideoValue),
     (Bookmark,      bookmark,         BookmarkValue),
+    (File,          file,             FileValue),
     (LinkPreview,   link_preview,      LinkPreviewValue),
     (Divider,       divider,          DividerValue),
     (Table,         table,            TableValue),
     (Embed,         embed,            EmbedValue),
+    (Equation,      equation,         EquationValue),
+    (ChildPage,     child_page,       ChildPageValue),
+    (ChildDatabase, child_database,   ChildDatabaseValue),
+    (LinkToPage,    link_to_page,     Parent),
+    (Breadcrumb,    breadcrump,       BreadcrumpValue),
+    (SyncedBlock,   synced_block,     SyncedBlockValue),
+    (Template,      template,         TemplateValue),
+    (TableOfContents, table_of_contents, TableOfContentsValue),
 }
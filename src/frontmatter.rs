@@ -0,0 +1,336 @@
+use crate::{
+    config::FrontmatterConfig,
+    notion_to_md::{NotionToMarkdown, PageMeta},
+    property::{property_to_cell_text_with, CheckboxStyle, MultiSelectStyle},
+    utils,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use notion_client::objects::page::PageProperty;
+use std::collections::HashMap;
+
+/// ページのプロパティから YAML frontmatter（`---` で囲まれたブロック）を生成する。
+///
+/// * `title` / `rich_text` / `select` は文字列として出力する
+/// * `multi_select` は YAML のシーケンスとして出力する（`Tags` → `tags:` など）
+/// * `number` / `checkbox` はクォートせず、YAML の数値・真偽値として出力する
+/// * キー名は `FrontmatterConfig::keys` で差し替えられる。未登録のプロパティは名前をそのまま使う
+pub fn page_to_frontmatter(
+    properties: &HashMap<String, PageProperty>,
+    config: &FrontmatterConfig,
+) -> String {
+    page_to_frontmatter_with_cover(properties, config, None)
+}
+
+/// [`page_to_frontmatter`] に加えて、ページのカバー画像の URL を `cover:` として先頭に出力する
+pub fn page_to_frontmatter_with_cover(
+    properties: &HashMap<String, PageProperty>,
+    config: &FrontmatterConfig,
+    cover: Option<&str>,
+) -> String {
+    page_to_frontmatter_with_meta(properties, config, cover, None)
+}
+
+/// [`page_to_frontmatter_with_cover`] に加えて、`FrontmatterConfig::dates` なら `meta` の
+/// 作成・更新日時を `date:` / `lastmod:` として出力する（RFC 3339、UTC）。
+/// `FrontmatterConfig::path` なら `meta.path` を `path:` のシーケンスとして出力する
+pub fn page_to_frontmatter_with_meta(
+    properties: &HashMap<String, PageProperty>,
+    config: &FrontmatterConfig,
+    cover: Option<&str>,
+    meta: Option<&PageMeta>,
+) -> String {
+    let mut names = properties.keys().collect::<Vec<_>>();
+    names.sort();
+
+    let mut yaml = String::from("---\n");
+    if let Some(cover) = cover {
+        yaml.push_str(&format!("cover: {}\n", yaml_string(cover)));
+    }
+    if let (true, Some(meta)) = (config.dates, meta) {
+        let timestamp = |time: &DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+        yaml.push_str(&format!("date: {}\n", timestamp(&meta.created_time)));
+        yaml.push_str(&format!("lastmod: {}\n", timestamp(&meta.last_edited_time)));
+    }
+    if let (true, Some(meta)) = (config.path, meta) {
+        if meta.path.is_empty() {
+            yaml.push_str("path: []\n");
+        } else {
+            yaml.push_str("path:\n");
+            for title in &meta.path {
+                yaml.push_str(&format!("  - {}\n", yaml_string(title)));
+            }
+        }
+    }
+    for name in names {
+        let key = config.keys.get(name).unwrap_or(name);
+        match &properties[name] {
+            PageProperty::Title {
+                title: rich_text, ..
+            }
+            | PageProperty::RichText { rich_text, .. } => {
+                let text = NotionToMarkdown::rich_text_to_markdown(rich_text);
+                yaml.push_str(&format!("{}: {}\n", key, yaml_string(&text)));
+            }
+            PageProperty::Select { select, .. } => {
+                if let Some(name) = select.as_ref().and_then(|s| s.name.as_ref()) {
+                    yaml.push_str(&format!("{}: {}\n", key, yaml_string(name)));
+                }
+            }
+            PageProperty::Number { number, .. } => match number {
+                Some(number) => yaml.push_str(&format!("{}: {}\n", key, number)),
+                None => yaml.push_str(&format!("{}: null\n", key)),
+            },
+            PageProperty::Checkbox { checkbox, .. } => {
+                yaml.push_str(&format!("{}: {}\n", key, checkbox));
+            }
+            PageProperty::MultiSelect { multi_select, .. } => {
+                if multi_select.is_empty() {
+                    yaml.push_str(&format!("{}: []\n", key));
+                    continue;
+                }
+                yaml.push_str(&format!("{}:\n", key));
+                for option in multi_select {
+                    if let Some(name) = &option.name {
+                        yaml.push_str(&format!("  - {}\n", yaml_string(name)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    yaml.push_str("---\n\n");
+    yaml
+}
+
+/// ページのプロパティを本文の先頭に置く 2 列（プロパティ・値）の Markdown テーブルにする。
+/// 値は `property_to_cell_text_with` と同じ書き方で、空のものは出力しない
+pub fn page_properties_table(
+    properties: &HashMap<String, PageProperty>,
+    multi_select: MultiSelectStyle,
+) -> String {
+    page_properties_table_with(properties, |property| {
+        property_to_cell_text_with(property, CheckboxStyle::default(), multi_select)
+    })
+}
+
+/// [`page_properties_table`] の値の書き方を `value` で決める
+pub fn page_properties_table_with<F>(properties: &HashMap<String, PageProperty>, value: F) -> String
+where
+    F: Fn(&PageProperty) -> String,
+{
+    let rows = property_values(properties, value);
+    if rows.is_empty() {
+        return String::new();
+    }
+    let cells = std::iter::once(vec!["Property".to_string(), "Value".to_string()])
+        .chain(
+            rows.iter()
+                .map(|(name, value)| vec![utils::table_cell(name), utils::table_cell(value)]),
+        )
+        .collect::<Vec<_>>();
+    let cells = cells
+        .iter()
+        .map(|row| row.iter().map(String::as_str).collect())
+        .collect::<Vec<_>>();
+    format!("{}\n\n", utils::aligned_table(&cells, &[], false))
+}
+
+/// ページのプロパティを定義リスト（`名前` の次の行に `: 値`）にする。
+/// PHP Markdown Extra や Pandoc の書き方で、扱うプロパティは [`page_properties_table`] と同じ
+pub fn page_properties_definition_list(
+    properties: &HashMap<String, PageProperty>,
+    multi_select: MultiSelectStyle,
+) -> String {
+    page_properties_definition_list_with(properties, |property| {
+        property_to_cell_text_with(property, CheckboxStyle::default(), multi_select)
+    })
+}
+
+/// [`page_properties_definition_list`] の値の書き方を `value` で決める
+pub fn page_properties_definition_list_with<F>(
+    properties: &HashMap<String, PageProperty>,
+    value: F,
+) -> String
+where
+    F: Fn(&PageProperty) -> String,
+{
+    property_values(properties, value)
+        .iter()
+        .map(|(name, value)| format!("{}\n: {}\n\n", name, value))
+        .collect()
+}
+
+// 本文に出力するプロパティの名前と値（名前順）。値が空のものは除く
+fn property_values<F>(properties: &HashMap<String, PageProperty>, value: F) -> Vec<(&str, String)>
+where
+    F: Fn(&PageProperty) -> String,
+{
+    let mut names = properties.keys().collect::<Vec<_>>();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| (name.as_str(), value(&properties[name])))
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+/// YAML のスカラーとして安全に出力できるよう、必要なときだけダブルクォートで囲む
+pub fn yaml_string(value: &str) -> String {
+    let needs_quote = value.is_empty()
+        || value.trim() != value
+        || value.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.contains('\n')
+        || matches!(
+            value.to_lowercase().as_str(),
+            "true" | "false" | "yes" | "no" | "null" | "~"
+        )
+        || value.parse::<f64>().is_ok();
+
+    if needs_quote {
+        format!(
+            "\"{}\"",
+            value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        )
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notion_client::objects::page::SelectPropertyValue;
+
+    fn select_option(name: &str) -> SelectPropertyValue {
+        SelectPropertyValue {
+            name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tags_multi_select_becomes_yaml_sequence() {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "Tags".to_string(),
+            PageProperty::MultiSelect {
+                id: None,
+                multi_select: vec![select_option("rust"), select_option("notion")],
+            },
+        );
+
+        assert_eq!(
+            page_to_frontmatter(&properties, &FrontmatterConfig::default()),
+            "---\ntags:\n  - rust\n  - notion\n---\n\n"
+        );
+    }
+
+    #[test]
+    fn custom_key_mapping() {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "カテゴリ".to_string(),
+            PageProperty::MultiSelect {
+                id: None,
+                multi_select: vec![select_option("日記")],
+            },
+        );
+        let mut config = FrontmatterConfig::default();
+        config
+            .keys
+            .insert("カテゴリ".to_string(), "categories".to_string());
+
+        assert_eq!(
+            page_to_frontmatter(&properties, &config),
+            "---\ncategories:\n  - 日記\n---\n\n"
+        );
+    }
+
+    #[test]
+    fn number_and_checkbox_are_typed() {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "count".to_string(),
+            PageProperty::Number {
+                id: None,
+                number: Some(serde_json::Number::from(5)),
+            },
+        );
+        properties.insert(
+            "draft".to_string(),
+            PageProperty::Checkbox {
+                id: None,
+                checkbox: true,
+            },
+        );
+
+        assert_eq!(
+            page_to_frontmatter(&properties, &FrontmatterConfig::default()),
+            "---\ncount: 5\ndraft: true\n---\n\n"
+        );
+    }
+
+    fn body_properties() -> HashMap<String, PageProperty> {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "Tags".to_string(),
+            PageProperty::MultiSelect {
+                id: None,
+                multi_select: vec![select_option("rust"), select_option("notion")],
+            },
+        );
+        properties.insert(
+            "Done".to_string(),
+            PageProperty::Checkbox {
+                id: None,
+                checkbox: false,
+            },
+        );
+        properties.insert(
+            "Score".to_string(),
+            PageProperty::Number {
+                id: None,
+                number: None,
+            },
+        );
+        properties
+    }
+
+    #[test]
+    fn properties_as_table() {
+        assert_eq!(
+            page_properties_table(&body_properties(), MultiSelectStyle::Comma),
+            "| Property | Value |\n| --- | --- |\n| Done | ✗ |\n| Tags | rust, notion |\n\n"
+        );
+        assert_eq!(
+            page_properties_table(&HashMap::new(), MultiSelectStyle::Comma),
+            ""
+        );
+    }
+
+    #[test]
+    fn properties_as_definition_list() {
+        assert_eq!(
+            page_properties_definition_list(&body_properties(), MultiSelectStyle::Comma),
+            "Done\n: ✗\n\nTags\n: rust, notion\n\n"
+        );
+        assert_eq!(
+            page_properties_definition_list(&body_properties(), MultiSelectStyle::Hashtag),
+            "Done\n: ✗\n\nTags\n: #rust #notion\n\n"
+        );
+    }
+
+    #[test]
+    fn yaml_string_quotes_ambiguous_values() {
+        assert_eq!(yaml_string("rust"), "rust");
+        assert_eq!(yaml_string("true"), "\"true\"");
+        assert_eq!(yaml_string("2024"), "\"2024\"");
+        assert_eq!(yaml_string("a: b"), "\"a: b\"");
+        assert_eq!(yaml_string("#tag"), "\"#tag\"");
+    }
+}
@@ -1,9 +1,17 @@
+#[cfg(feature = "asset-download")]
+pub mod assets;
 pub mod builder;
+pub mod client;
+pub mod config;
 pub mod converters;
+pub mod frontmatter;
 pub mod notion_to_md;
+pub mod property;
+pub mod source;
 pub mod utils;
 
 pub mod notion_client {
     pub use notion_client::endpoints::*;
     pub use notion_client::objects::*;
+    pub use notion_client::NotionClientError;
 }
@@ -1,130 +1,1354 @@
-use crate::converters::Converters;
+use crate::{
+    config::{
+        Config, DateFormat, DividerSpacing, FrontmatterConfig, HtmlMode, OutputFormat, PageCover,
+        PropertyOutput, TemplateMentionStyle,
+    },
+    converters::{ConvContext, Converters},
+    frontmatter::{
+        page_properties_definition_list_with, page_properties_table_with,
+        page_to_frontmatter_with_meta,
+    },
+    property::{property_to_cell_text_with, relation_links, CheckboxStyle},
+    source::{BlockSource, DatabaseView, LinkMetadata, NotionApiSource},
+    utils,
+};
 use anyhow::Result;
-use futures::future::BoxFuture;
+use chrono::{DateTime, Timelike, Utc};
+use futures::{future::BoxFuture, stream, StreamExt, TryStreamExt};
 use notion_client::{
     endpoints::Client,
     objects::{
-        block::{Block, BlockType},
+        block::{Block, BlockType, ParagraphValue},
+        comment::Comment,
         file::File,
+        page::{Page, PageProperty},
+        parent::Parent,
+        property::DatePropertyValue,
+        rich_text::{Mention, RichText, TemplateMention, TemplateMentionDate, Text},
     },
+    NotionClientError,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock},
 };
 
 #[derive(Debug)]
 pub struct BlockWithChildren {
     pub block: Block,
     pub children: Vec<BlockWithChildren>,
+    // include_comments が有効なときだけ取得される
+    pub comments: Vec<Comment>,
+}
+
+impl BlockWithChildren {
+    /// このブロックと子孫を深さ優先（Notion 上の表示順）でたどる
+    pub fn iter(&self) -> Blocks<'_> {
+        Blocks { stack: vec![self] }
+    }
+}
+
+/// 取得したブロックツリー全体を深さ優先（Notion 上の表示順）でたどる
+pub fn blocks(tree: &[BlockWithChildren]) -> Blocks<'_> {
+    Blocks {
+        stack: tree.iter().rev().collect(),
+    }
+}
+
+/// [`blocks`] / [`BlockWithChildren::iter`] が返すイテレータ
+pub struct Blocks<'a> {
+    stack: Vec<&'a BlockWithChildren>,
+}
+
+impl<'a> Iterator for Blocks<'a> {
+    type Item = &'a Block;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(&node.block)
+    }
+}
+
+/// ページに含まれるファイルの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    Image,
+    Video,
+    File,
+    Pdf,
+}
+
+/// ページに含まれるファイルへの参照。事前のダウンロードや URL の検証に使う
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetRef {
+    pub block_id: String,
+    pub kind: AssetKind,
+    pub url: String,
+}
+
+/// 変換したページの情報。静的サイトジェネレーターでのファイル名や日付に使う
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageMeta {
+    pub id: String,
+    /// タイトルのプロパティのプレーンテキスト
+    pub title: String,
+    pub url: String,
+    pub created_time: DateTime<Utc>,
+    pub last_edited_time: DateTime<Utc>,
+    /// 祖先ページ（データベースを含む）のタイトル。最上位から順で、このページは含まない。
+    /// `FrontmatterConfig::path` のときだけ解決し、それ以外は空
+    pub path: Vec<String>,
 }
+
+impl PageMeta {
+    pub fn from_page(page: &Page) -> Self {
+        let title = page
+            .properties
+            .values()
+            .find_map(|property| match property {
+                PageProperty::Title { title, .. } => Some(NotionToMarkdown::plain_text(title)),
+                _ => None,
+            })
+            .unwrap_or_default();
+        Self {
+            id: page.id.clone(),
+            title,
+            url: page.url.clone(),
+            created_time: page.created_time,
+            last_edited_time: page.last_edited_time,
+            path: Vec::new(),
+        }
+    }
+}
+
+/// 取得したブロックツリーに含まれる画像・動画・ファイル・PDF を表示順に集める
+pub fn assets(tree: &[BlockWithChildren]) -> Vec<AssetRef> {
+    blocks(tree)
+        .filter_map(|block| {
+            let (kind, file) = match &block.block_type {
+                BlockType::Image { image } => (AssetKind::Image, &image.file_type),
+                BlockType::Video { video } => (AssetKind::Video, &video.file_type),
+                BlockType::File { file } => (AssetKind::File, &file.file_type),
+                BlockType::Pdf { pdf } => (AssetKind::Pdf, &pdf.file_type),
+                _ => return None,
+            };
+            Some(AssetRef {
+                block_id: block.id.clone().unwrap_or_default(),
+                kind,
+                url: NotionToMarkdown::get_file_url(file),
+            })
+        })
+        .collect()
+}
+
+/// 目次の項目。見出しのアンカーと同じスラッグを使う
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocEntry {
+    /// 1〜3（heading_1〜heading_3）
+    pub level: usize,
+    pub text: String,
+    pub slug: String,
+}
+
+/// 変換の前に文書全体から集める情報。後ろにあるものを参照する出力（目次など）に使う
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentContext {
+    /// 文書中の見出し（出現順）
+    pub headings: Vec<TocEntry>,
+}
+
+impl DocumentContext {
+    /// 収集パス。ブロックツリーをたどるだけで、変換や API の呼び出しはしない
+    pub fn collect(tree: &[BlockWithChildren]) -> Self {
+        Self {
+            headings: toc_entries(tree),
+        }
+    }
+}
+
+/// 1 回の変換（`convert_blocks_to_markdown` / `render_tree`）の間だけ使う状態。
+/// 変換ごとに作り直すので、同じ `NotionToMarkdown` で並行して変換しても混ざらない。
+/// コンバータには `ConvFuncPayload::conversion` で渡る
+#[derive(Debug, Default)]
+pub struct ConversionContext {
+    // 変換の前の収集パスで集めた情報
+    document: DocumentContext,
+    // 子ブロックを変換中の祖先ブロックの種類（外側から順に）
+    parents: Mutex<Vec<BlockType>>,
+    // heading_anchors のときに使ったスラッグ
+    slugs: Mutex<utils::Slugger>,
+    // 出力済みの同期ブロックの同期元 ID
+    synced: Mutex<HashSet<String>>,
+    // image_references のときに参照した画像の URL（出現順。番号が参照 ID になる）
+    image_refs: Mutex<Vec<String>>,
+    // summarize_unsupported のときの未対応ブロックの種類 → 件数
+    unsupported: Mutex<HashMap<String, usize>>,
+}
+
+impl ConversionContext {
+    /// 収集パスの結果。`two_pass` でなければ、目次ブロックがあるときだけ集める
+    pub fn document(&self) -> &DocumentContext {
+        &self.document
+    }
+
+    /// 文書中の見出し。[`document`](Self::document) の `headings`
+    pub fn toc_entries(&self) -> &[TocEntry] {
+        &self.document.headings
+    }
+
+    /// `image_references` のときの画像の参照 ID（`image-1`, `image-2`, ...）。
+    /// 同じ URL には同じ ID を返し、定義は変換の最後に 1 回だけ出力する
+    pub fn image_reference(&self, url: &str) -> String {
+        let mut refs = self.image_refs.lock().unwrap();
+        let index = match refs.iter().position(|known| known == url) {
+            Some(index) => index,
+            None => {
+                refs.push(url.to_string());
+                refs.len() - 1
+            }
+        };
+        format!("image-{}", index + 1)
+    }
+
+    /// 同期ブロックの同期元を出力済みとして記録する。この変換で初めてなら `true`
+    pub fn mark_synced_rendered(&self, original_id: &str) -> bool {
+        self.synced
+            .lock()
+            .unwrap()
+            .insert(utils::normalize_notion_id(original_id))
+    }
+
+    /// 見出しのアンカーにするスラッグ。同じ変換の中で重複したら `-1`, `-2`, ... を付ける。
+    /// スラッグが空になる見出しは `None`
+    pub fn heading_slug(&self, text: &str) -> Option<String> {
+        Some(self.slugs.lock().unwrap().slug(text)).filter(|slug| !slug.is_empty())
+    }
+
+    /// 変換中のブロックの親と入れ子の深さ
+    pub(crate) fn conv_context(&self) -> ConvContext {
+        let parents = self.parents.lock().unwrap();
+        ConvContext {
+            parent: parents.last().cloned(),
+            depth: parents.len(),
+        }
+    }
+
+    pub(crate) fn push_parent(&self, block_type: &BlockType) {
+        self.parents.lock().unwrap().push(block_type.clone());
+    }
+
+    pub(crate) fn pop_parent(&self) {
+        self.parents.lock().unwrap().pop();
+    }
+}
+
+// 文書中の見出しを出現順に集める。スラッグは変換時の heading_slug と同じ順で振る
+fn toc_entries(tree: &[BlockWithChildren]) -> Vec<TocEntry> {
+    let mut slugger = utils::Slugger::new();
+    blocks(tree)
+        .filter_map(|block| {
+            let (level, heading) = match &block.block_type {
+                BlockType::Heading1 { heading_1 } => (1, heading_1),
+                BlockType::Heading2 { heading_2 } => (2, heading_2),
+                BlockType::Heading3 { heading_3 } => (3, heading_3),
+                _ => return None,
+            };
+            let text = NotionToMarkdown::plain_text(&heading.rich_text);
+            let slug = slugger.slug(&text);
+            (!slug.is_empty()).then_some(TocEntry { level, text, slug })
+        })
+        .collect()
+}
+
 pub struct NotionToMarkdown {
-    client: Client,
+    pub(crate) source: Arc<dyn BlockSource>,
+    // link_to_page などのリンク先 ID → タイトル。取得時に埋める。
+    // ID をキーにする表・集合はハイフンの有無をそろえるため utils::normalize_notion_id で正規化する
+    titles: RwLock<HashMap<String, String>>,
+    // ブックマーク・リンクプレビューの URL → Open Graph 情報。
+    // bookmark_as_card / link_preview_titles のときだけ取得時に埋める
+    link_cards: RwLock<HashMap<String, LinkMetadata>>,
+    // file ブロックの URL（書き換え前）→ バイト数。file_sizes のときだけ取得時に埋める
+    file_sizes: RwLock<HashMap<String, u64>>,
+    // child_database のブロック ID → ビューのフィルタ・並べ替え。
+    // database_view_comments のときだけ取得時に埋める
+    database_views: RwLock<HashMap<String, DatabaseView>>,
+    // パンくずリストのブロック ID → 祖先のページの (ID, タイトル)。取得時に埋める
+    ancestries: RwLock<HashMap<String, Vec<(String, String)>>>,
     pub converters: Converters,
+    pub config: Config,
 }
 
 impl NotionToMarkdown {
-    pub fn new(
-        notion_client: Client,
-        converters: Converters,
-    ) -> Self {
+    pub fn new(notion_client: Client, converters: Converters, config: Config) -> Self {
         NotionToMarkdown {
-            client: notion_client,
+            source: Arc::new(NotionApiSource::new(notion_client)),
+            titles: RwLock::new(HashMap::new()),
+            link_cards: RwLock::new(HashMap::new()),
+            file_sizes: RwLock::new(HashMap::new()),
+            database_views: RwLock::new(HashMap::new()),
+            ancestries: RwLock::new(HashMap::new()),
             converters,
+            config,
         }
     }
 
+    /// ブラウザからコピーした Notion の URL のページを変換する。
+    /// ページ ID は `utils::extract_page_id` で取り出す
+    pub async fn convert_page_url(&self, url: &str) -> Result<String> {
+        let page_id = utils::extract_page_id(url)
+            .ok_or_else(|| anyhow::anyhow!("no Notion page id in URL: {}", url))?;
+        self.convert_page(&page_id).await
+    }
+
     pub async fn convert_page(&self, page_id: &str) -> Result<String> {
-        let blocks = self.get_block_children_recursively(page_id).await?;
-        let content = self.convert_blocks_to_markdown(&blocks)?;
+        let blocks = self.fetch_blocks(page_id).await?;
+        let body = self.render_tree(&blocks)?;
+
+        let properties_in_body = matches!(
+            self.config.property_output,
+            PropertyOutput::Table | PropertyOutput::DefinitionList
+        );
+        if self.page_frontmatter().is_none()
+            && !properties_in_body
+            && self.config.page_cover == PageCover::Omit
+        {
+            return Ok(self.finish(&body));
+        }
+
+        let page = self.source.retrieve_page(page_id).await?;
+        let meta = self.page_meta(&page).await?;
+        let mut content = self.page_header(&page, &meta).await?;
+        content.push_str(&body);
+        Ok(self.finish(&content))
+    }
+
+    /// [`convert_page`](Self::convert_page) と同じ変換をし、ページの作成・更新日時などを一緒に返す
+    pub async fn convert_page_with_meta(&self, page_id: &str) -> Result<(String, PageMeta)> {
+        let blocks = self.fetch_blocks(page_id).await?;
+        let body = self.render_tree(&blocks)?;
+        let page = self.source.retrieve_page(page_id).await?;
+        let meta = self.page_meta(&page).await?;
+        let mut content = self.page_header(&page, &meta).await?;
+        content.push_str(&body);
+        Ok((self.finish(&content), meta))
+    }
+
+    // frontmatter の path が有効なら、祖先ページのタイトルも解決する
+    async fn page_meta(&self, page: &Page) -> Result<PageMeta> {
+        let mut meta = PageMeta::from_page(page);
+        if self
+            .page_frontmatter()
+            .is_some_and(|frontmatter| frontmatter.path)
+        {
+            let mut ancestry = self.resolve_ancestry(&page.id).await?;
+            // resolve_ancestry の最後はこのページ自身
+            ancestry.pop();
+            meta.path = ancestry.into_iter().map(|(_, title)| title).collect();
+        }
+        Ok(meta)
+    }
+
+    // 変換の最後に文書全体へかける変換（改行コード）
+    fn finish(&self, document: &str) -> String {
+        utils::apply_line_ending(document, self.config.line_ending)
+    }
+
+    // property_output が Frontmatter 以外なら frontmatter にはプロパティを出力しない
+    fn page_frontmatter(&self) -> Option<&FrontmatterConfig> {
+        match self.config.property_output {
+            PropertyOutput::Frontmatter => self.config.frontmatter.as_ref(),
+            _ => None,
+        }
+    }
+
+    // 本文の前に置く frontmatter・カバー画像・プロパティ
+    async fn page_header(&self, page: &Page, meta: &PageMeta) -> Result<String> {
+        let frontmatter = self.page_frontmatter();
+        let cover = match (&page.cover, self.config.page_cover) {
+            (_, PageCover::Omit) | (None, _) => None,
+            (Some(cover), _) => Some(self.file_url(cover)?),
+        };
+
+        let mut content = String::new();
+        match (self.config.page_cover, frontmatter) {
+            (PageCover::Frontmatter, frontmatter) if cover.is_some() => {
+                // frontmatter が無効でも、カバー画像だけの frontmatter を出力する
+                let default = FrontmatterConfig::default();
+                let (properties, frontmatter) = match frontmatter {
+                    Some(frontmatter) => (page.properties.clone(), frontmatter),
+                    None => (HashMap::new(), &default),
+                };
+                content.push_str(&page_to_frontmatter_with_meta(
+                    &properties,
+                    frontmatter,
+                    cover.as_deref(),
+                    Some(meta),
+                ));
+            }
+            (_, Some(frontmatter)) => {
+                content.push_str(&page_to_frontmatter_with_meta(
+                    &page.properties,
+                    frontmatter,
+                    None,
+                    Some(meta),
+                ));
+            }
+            _ => {}
+        }
+        if let (PageCover::Image, Some(cover)) = (self.config.page_cover, &cover) {
+            content.push_str(&format!("![cover]({})\n\n", cover));
+        }
+        match self.config.property_output {
+            PropertyOutput::Table => {
+                self.resolve_relation_titles(page.properties.values()).await;
+                content.push_str(&page_properties_table_with(&page.properties, |property| {
+                    self.property_markdown(property)
+                }));
+            }
+            PropertyOutput::DefinitionList => {
+                self.resolve_relation_titles(page.properties.values()).await;
+                content.push_str(&page_properties_definition_list_with(
+                    &page.properties,
+                    |property| self.property_markdown(property),
+                ));
+            }
+            PropertyOutput::Frontmatter | PropertyOutput::None => {}
+        }
         Ok(content)
     }
 
+    /// 指定したブロック（と子孫）を取得し、指定の順に 1 つの文書として変換する。
+    /// 別々のページにあるブロックを集めて文書を組み立てるのに使う
+    pub async fn convert_block_ids(&self, ids: &[&str]) -> Result<String> {
+        let blocks = stream::iter(ids)
+            .map(|id| async move {
+                let block = self.retrying(|| self.source.retrieve_block(id)).await?;
+                self.fetch_block_with_children(block, 1).await
+            })
+            .buffered(self.config.fetch_concurrency.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(self.finish(&self.render_tree(&blocks)?))
+    }
+
+    /// ページ（またはブロック）配下のブロックツリーを取得する。
+    /// 子要素は `fetch_concurrency` 件まで並行して取得するが、結果の順序は Notion 上の順序のまま。
+    /// `iterative_fetch` が有効なら `fetch_blocks_iterative` で取得する
+    pub async fn fetch_blocks(&self, block_id: &str) -> Result<Vec<BlockWithChildren>> {
+        let tree = if self.config.iterative_fetch {
+            self.fetch_blocks_iterative(block_id).await?
+        } else {
+            self.get_block_children_recursively(block_id, 1).await?
+        };
+
+        let breadcrumbs = blocks(&tree)
+            .filter(|block| matches!(block.block_type, BlockType::Breadcrumb { .. }))
+            .filter_map(|block| block.id.clone())
+            .collect::<Vec<_>>();
+        if !breadcrumbs.is_empty() {
+            let ancestry = self.resolve_ancestry(block_id).await?;
+            let mut map = self.ancestries.write().unwrap();
+            for id in breadcrumbs {
+                map.insert(utils::normalize_notion_id(&id), ancestry.clone());
+            }
+        }
+
+        Ok(tree)
+    }
+
+    // ページから親をたどり、最上位から順に (ID, タイトル) を並べる。データベースに着いたらそこで止める
+    async fn resolve_ancestry(&self, page_id: &str) -> Result<Vec<(String, String)>> {
+        const MAX_ANCESTORS: usize = 32;
+
+        let mut ancestry = Vec::new();
+        let mut current = Parent::PageId {
+            page_id: page_id.to_string(),
+        };
+        while ancestry.len() < MAX_ANCESTORS {
+            let id = match &current {
+                Parent::PageId { page_id } => page_id.clone(),
+                Parent::DatabaseId { database_id } => database_id.clone(),
+                _ => break,
+            };
+            self.resolve_title(&current).await?;
+            let title = self.title_of(&id).unwrap_or_else(|| "Untitled".to_string());
+            ancestry.push((id.clone(), title));
+
+            if matches!(current, Parent::DatabaseId { .. }) {
+                break;
+            }
+            match self.source.retrieve_page_parent(&id).await? {
+                Some(parent) => current = parent,
+                None => break,
+            }
+        }
+
+        ancestry.reverse();
+        Ok(ancestry)
+    }
+
+    /// パンくずリストのブロックに対応する、最上位から順のページの (ID, タイトル)
+    pub fn ancestry_of(&self, breadcrumb_id: &str) -> Vec<(String, String)> {
+        self.ancestries
+            .read()
+            .unwrap()
+            .get(&utils::normalize_notion_id(breadcrumb_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// `fetch_blocks` と同じツリーを、再帰せずに階層ごとの作業キューで取得する。
+    /// 再帰版は階層ごとに future が入れ子になるため、深さ数百を超えるツリーでは
+    /// スタックが溢れうる。こちらは深さに上限がなく（`max_depth` を除く）、
+    /// 同じ階層のブロックは `fetch_concurrency` 件まで並行して取得する。
+    /// ただし変換（`render_tree`）は階層ごとに再帰するので、極端に深いツリーは
+    /// 変換時にスタックを消費する点に注意
+    pub async fn fetch_blocks_iterative(&self, block_id: &str) -> Result<Vec<BlockWithChildren>> {
+        // nodes は取得順に並ぶ。子は必ず親より後ろに入るので、末尾から組み立てれば再帰は要らない
+        let mut nodes: Vec<(Block, Vec<Comment>)> = Vec::new();
+        let mut children: Vec<Vec<usize>> = Vec::new();
+        let mut roots = Vec::new();
+
+        // (子を取得するブロックの ID, 親のノード番号, 子の階層)
+        let mut level = vec![(block_id.to_string(), None::<usize>, 1)];
+        while !level.is_empty() {
+            let fetched = stream::iter(level)
+                .map(|(id, parent, depth)| async move {
+                    let blocks = match self.list_block_children(&id).await {
+                        Err(e)
+                            if parent.is_some()
+                                && self.config.skip_inaccessible
+                                && is_inaccessible(&e) =>
+                        {
+                            log::warn!("Skipping inaccessible children of block {}: {}", id, e);
+                            let placeholder = inaccessible_placeholder(&id);
+                            return Ok((vec![(placeholder.block, Vec::new())], parent, depth));
+                        }
+                        result => result?,
+                    };
+                    let mut results = Vec::with_capacity(blocks.len());
+                    for block in blocks {
+                        let comments = self.fetch_block_extras(&block).await?;
+                        results.push((block, comments));
+                    }
+                    anyhow::Ok((results, parent, depth))
+                })
+                .buffered(self.config.fetch_concurrency.max(1))
+                .try_collect::<Vec<_>>()
+                .await?;
+
+            let mut next = Vec::new();
+            for (blocks, parent, depth) in fetched {
+                for (block, comments) in blocks {
+                    let index = nodes.len();
+                    match parent {
+                        Some(parent) => children[parent].push(index),
+                        None => roots.push(index),
+                    }
+                    if let (Some(id), true) = (&block.id, block.has_children.unwrap_or(false)) {
+                        if self.config.max_depth.is_some_and(|max| depth >= max) {
+                            log::warn!(
+                                "Max depth {} reached; children of block {} are not fetched",
+                                depth,
+                                id
+                            );
+                        } else {
+                            next.push((id.clone(), Some(index), depth + 1));
+                        }
+                    }
+                    nodes.push((block, comments));
+                    children.push(Vec::new());
+                }
+            }
+            level = next;
+        }
+
+        let mut built: Vec<Option<BlockWithChildren>> = Vec::with_capacity(nodes.len());
+        built.resize_with(nodes.len(), || None);
+        for (index, (block, comments)) in nodes.into_iter().enumerate().rev() {
+            let children = children[index]
+                .iter()
+                .filter_map(|&child| built[child].take())
+                .collect();
+            built[index] = Some(BlockWithChildren {
+                block,
+                children,
+                comments,
+            });
+        }
+        Ok(roots
+            .into_iter()
+            .filter_map(|index| built[index].take())
+            .collect())
+    }
+
+    // ページネーションをたどって、ブロック直下の子ブロックをすべて取得する
+    async fn list_block_children(&self, block_id: &str) -> Result<Vec<Block>> {
+        let mut results = Vec::new();
+        let mut start_cursor = None;
+
+        loop {
+            let response = self
+                .retrying(|| {
+                    self.source
+                        .retrieve_block_children(block_id, start_cursor.as_deref())
+                })
+                .await?;
+            results.extend(response.results);
+
+            if !response.has_more {
+                break;
+            }
+            start_cursor = response.next_cursor;
+        }
+
+        Ok(results)
+    }
+
+    // depth は取得するブロックの階層（ページ直下が 1）
     fn get_block_children_recursively<'a>(
         &'a self,
         block_id: &'a str,
+        depth: usize,
     ) -> BoxFuture<'a, Result<Vec<BlockWithChildren>>> {
         Box::pin(async move {
-            let mut blocks = Vec::new();
-            let mut start_cursor = None;
-
-            loop {
-                let response = self
-                    .client
-                    .blocks
-                    .retrieve_block_children(block_id, start_cursor.as_deref(), None)
-                    .await?;
-                // .map_err(|e| NotionToObsidianError::BlockRetrievalError(e.to_string()))?;
-
-                for block in response.results {
-                    let children = if block.has_children.unwrap_or(false) {
-                        if let Some(id) = &block.id {
-                            self.get_block_children_recursively(id).await?
-                        } else {
-                            Vec::new()
-                        }
-                    } else {
-                        Vec::new()
-                    };
+            let results = self.list_block_children(block_id).await?;
 
-                    blocks.push(BlockWithChildren { block, children });
-                }
+            // buffered は完了順ではなく投入順に結果を返すので、並行取得しても順序は変わらない
+            stream::iter(results)
+                .map(|block| self.fetch_block_with_children(block, depth))
+                .buffered(self.config.fetch_concurrency.max(1))
+                .try_collect()
+                .await
+        })
+    }
+
+    /// データベースの全行を取得する。`has_more` / `next_cursor` をたどり、429 は再試行する
+    pub async fn query_database(&self, database_id: &str) -> Result<Vec<Page>> {
+        let mut pages = Vec::new();
+        let mut start_cursor: Option<String> = None;
+
+        loop {
+            let response = self
+                .retrying(|| {
+                    self.source
+                        .query_database(database_id, start_cursor.as_deref())
+                })
+                .await?;
+            pages.extend(response.results);
+
+            if !response.has_more {
+                break;
+            }
+            start_cursor = response.next_cursor;
+        }
+
+        Ok(pages)
+    }
+
+    /// データベースの行をプロパティのテーブルとして出力する。タイトルの列が先頭、残りは名前順
+    pub async fn convert_database(&self, database_id: &str) -> Result<String> {
+        let pages = self.query_database(database_id).await?;
+        let Some(first) = pages.first() else {
+            return Ok(String::new());
+        };
+        self.resolve_relation_titles(pages.iter().flat_map(|page| page.properties.values()))
+            .await;
+
+        let columns = database_columns(first);
+
+        let mut rows = vec![columns
+            .iter()
+            .map(|name| utils::table_cell(name))
+            .collect::<Vec<_>>()];
+        for page in &pages {
+            rows.push(
+                columns
+                    .iter()
+                    .map(|name| {
+                        page.properties
+                            .get(name)
+                            .map(|property| utils::table_cell(&self.property_markdown(property)))
+                            .unwrap_or_default()
+                    })
+                    .collect(),
+            );
+        }
+        let rows = rows
+            .iter()
+            .map(|row| row.iter().map(String::as_str).collect())
+            .collect::<Vec<Vec<&str>>>();
+        Ok(self.finish(&format!("{}\n\n", utils::table(&rows))))
+    }
+
+    /// ページの画像・動画・ファイル・PDF の URL を集める。Markdown には変換しない。
+    /// Notion にホストされたファイルの URL は失効するので、取得後すぐに使うこと
+    pub async fn collect_assets(&self, page_id: &str) -> Result<Vec<AssetRef>> {
+        let blocks = self.fetch_blocks(page_id).await?;
+        Ok(assets(&blocks))
+    }
+
+    /// データベースの行を CSV として出力する。1 行目はプロパティ名で、列の順は
+    /// `convert_database` と同じ。カンマ・改行・ダブルクォートを含む値はクォートする
+    pub async fn convert_database_to_csv(&self, database_id: &str) -> Result<String> {
+        let pages = self.query_database(database_id).await?;
+        let Some(first) = pages.first() else {
+            return Ok(String::new());
+        };
+        self.resolve_relation_titles(pages.iter().flat_map(|page| page.properties.values()))
+            .await;
 
-                if !response.has_more {
-                    break;
+        let columns = database_columns(first);
+        let mut csv = utils::csv_row(&columns);
+        for page in &pages {
+            let row = columns
+                .iter()
+                .map(|name| {
+                    page.properties
+                        .get(name)
+                        .map(|property| self.property_plain_text(property))
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>();
+            csv.push_str(&utils::csv_row(&row));
+        }
+        Ok(self.finish(&csv))
+    }
+
+    // レート制限（429）で失敗したら retry_base_delay から倍々に待って再試行する
+    async fn retrying<'f, T>(&self, op: impl Fn() -> BoxFuture<'f, Result<T>>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.config.max_retries && is_rate_limited(&e) => {
+                    let delay = self.config.retry_base_delay * 2u32.pow(attempt as u32);
+                    log::warn!("Rate limited; retrying in {:?}: {}", delay, e);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
                 }
-                start_cursor = response.next_cursor;
+                result => return result,
             }
+        }
+    }
 
-            Ok(blocks)
+    async fn fetch_block_with_children(
+        &self,
+        block: Block,
+        depth: usize,
+    ) -> Result<BlockWithChildren> {
+        let exceeds_depth = self.config.max_depth.is_some_and(|max| depth >= max);
+        let children = match (&block.id, block.has_children.unwrap_or(false)) {
+            (Some(id), true) if exceeds_depth => {
+                log::warn!(
+                    "Max depth {} reached; children of block {} are not fetched",
+                    depth,
+                    id
+                );
+                Vec::new()
+            }
+            (Some(id), true) => match self.get_block_children_recursively(id, depth + 1).await {
+                Err(e) if self.config.skip_inaccessible && is_inaccessible(&e) => {
+                    log::warn!("Skipping inaccessible children of block {}: {}", id, e);
+                    vec![inaccessible_placeholder(id)]
+                }
+                result => result?,
+            },
+            _ => Vec::new(),
+        };
+        let comments = self.fetch_block_extras(&block).await?;
+
+        Ok(BlockWithChildren {
+            block,
+            children,
+            comments,
         })
     }
 
+    // リンク先のタイトルやリンクカードなど、変換時に参照するブロックごとの付随情報を取得し、
+    // include_comments が有効ならブロックのコメントを返す
+    async fn fetch_block_extras(&self, block: &Block) -> Result<Vec<Comment>> {
+        if let BlockType::LinkToPage { link_to_page } = &block.block_type {
+            self.resolve_title(link_to_page).await?;
+        }
+        match &block.block_type {
+            BlockType::Bookmark { bookmark } if self.config.bookmark_as_card => {
+                self.resolve_link_card(&bookmark.url).await;
+            }
+            BlockType::LinkPreview { link_preview } if self.config.link_preview_titles => {
+                self.resolve_link_card(&link_preview.url).await;
+            }
+            BlockType::File { file } if self.config.file_sizes => {
+                self.resolve_file_size(&Self::get_file_url(&file.file_type))
+                    .await;
+            }
+            _ => {}
+        }
+        if let (BlockType::ChildDatabase { .. }, Some(id), true) = (
+            &block.block_type,
+            &block.id,
+            self.config.database_view_comments,
+        ) {
+            if let Some(view) = self.source.retrieve_database_view(id).await? {
+                self.database_views
+                    .write()
+                    .unwrap()
+                    .insert(utils::normalize_notion_id(id), view);
+            }
+        }
+
+        match (&block.id, self.config.include_comments) {
+            (Some(id), true) => self.get_block_comments(id).await,
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    async fn resolve_title(&self, target: &Parent) -> Result<()> {
+        let id = match target {
+            Parent::DatabaseId { database_id } => database_id,
+            Parent::PageId { page_id } => page_id,
+            _ => return Ok(()),
+        };
+        if self.title_of(id).is_some() {
+            return Ok(());
+        }
+        if let Some(title) = self.source.retrieve_title(target).await? {
+            self.titles
+                .write()
+                .unwrap()
+                .insert(utils::normalize_notion_id(id), title);
+        }
+        Ok(())
+    }
+
+    // resolve_relation_titles のときだけ、relation の関連ページのタイトルを取得しておく。
+    // 取得できないページ（共有されていないなど）はページ ID のまま出力する
+    async fn resolve_relation_titles<'p, I>(&self, properties: I)
+    where
+        I: IntoIterator<Item = &'p PageProperty>,
+    {
+        if !self.config.resolve_relation_titles {
+            return;
+        }
+        for property in properties {
+            let PageProperty::Relation { relation, .. } = property else {
+                continue;
+            };
+            for page in relation {
+                let target = Parent::PageId {
+                    page_id: page.id.clone(),
+                };
+                if let Err(e) = self.resolve_title(&target).await {
+                    log::warn!("Failed to fetch the title of {}: {}", page.id, e);
+                }
+            }
+        }
+    }
+
+    // 取得に失敗しても変換は続け、そのブックマーク・リンクプレビューは通常のリンクとして出力する
+    async fn resolve_link_card(&self, url: &str) {
+        if self.link_card_of(url).is_some() {
+            return;
+        }
+        match self.source.retrieve_link_metadata(url).await {
+            Ok(Some(metadata)) => {
+                self.link_cards
+                    .write()
+                    .unwrap()
+                    .insert(url.to_string(), metadata);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to fetch link metadata for {}: {}", url, e),
+        }
+    }
+
+    // 取得に失敗してもサイズなしのリンクとして出力する
+    async fn resolve_file_size(&self, url: &str) {
+        if self.file_size_of(url).is_some() {
+            return;
+        }
+        match self.source.retrieve_file_size(url).await {
+            Ok(Some(size)) => {
+                self.file_sizes
+                    .write()
+                    .unwrap()
+                    .insert(url.to_string(), size);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to fetch the size of {}: {}", url, e),
+        }
+    }
+
+    /// 取得済みのファイルのサイズ（`file_sizes` のとき）。`url` は書き換え前の URL
+    pub fn file_size_of(&self, url: &str) -> Option<u64> {
+        self.file_sizes.read().unwrap().get(url).copied()
+    }
+
+    /// 取得済みのブックマーク・リンクプレビューの Open Graph 情報
+    pub fn link_card_of(&self, url: &str) -> Option<LinkMetadata> {
+        self.link_cards.read().unwrap().get(url).cloned()
+    }
+
+    // データベースのセル・CSV・本文に出力するプロパティの値
+    fn property_text(&self, property: &PageProperty) -> String {
+        if let PageProperty::Relation { relation, .. } = property {
+            return relation_links(relation, |id| {
+                self.title_of(id)
+                    .map(|title| utils::escape_text(&title, &self.config))
+            });
+        }
+        property_to_cell_text_with(
+            property,
+            CheckboxStyle::default(),
+            self.config.multi_select_style,
+        )
+    }
+
+    // CSV に出力するプロパティの値。装飾やリンクの Markdown を含めず、
+    // relation は関連ページのタイトル（取得していなければページ ID）をカンマでつなぐ
+    fn property_plain_text(&self, property: &PageProperty) -> String {
+        match property {
+            PageProperty::Title {
+                title: rich_text, ..
+            }
+            | PageProperty::RichText { rich_text, .. } => Self::plain_text(rich_text),
+            PageProperty::Relation { relation, .. } => relation
+                .iter()
+                .map(|page| self.title_of(&page.id).unwrap_or_else(|| page.id.clone()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => property_to_cell_text_with(
+                property,
+                CheckboxStyle::default(),
+                self.config.multi_select_style,
+            ),
+        }
+    }
+
+    // Markdown のテーブル・定義リストに出力するプロパティの値。
+    // render_color のときは select / status の選択肢を Notion の色のバッジにする
+    fn property_markdown(&self, property: &PageProperty) -> String {
+        match property {
+            PageProperty::Select {
+                select: Some(option),
+                ..
+            }
+            | PageProperty::Status {
+                status: Some(option),
+                ..
+            } if self.config.render_color && self.config.html_mode == HtmlMode::Inline => {
+                let name = option.name.as_deref().unwrap_or_default();
+                match &option.color {
+                    Some(color) if !name.is_empty() => utils::badge(name, color),
+                    _ => name.to_string(),
+                }
+            }
+            _ => self.property_text(property),
+        }
+    }
+
+    /// 取得済みの child_database ブロックのビューのフィルタ・並べ替え
+    pub fn database_view_of(&self, block_id: &str) -> Option<DatabaseView> {
+        self.database_views
+            .read()
+            .unwrap()
+            .get(&utils::normalize_notion_id(block_id))
+            .cloned()
+    }
+
+    /// 取得済みのページ・データベースのタイトル
+    pub fn title_of(&self, id: &str) -> Option<String> {
+        self.titles
+            .read()
+            .unwrap()
+            .get(&utils::normalize_notion_id(id))
+            .cloned()
+    }
+
+    async fn get_block_comments(&self, block_id: &str) -> Result<Vec<Comment>> {
+        let mut comments = Vec::new();
+        let mut start_cursor = None;
+
+        loop {
+            let response = self
+                .retrying(|| {
+                    self.source
+                        .retrieve_comments(block_id, start_cursor.as_deref())
+                })
+                .await?;
+            comments.extend(response.results);
+
+            if !response.has_more {
+                break;
+            }
+            start_cursor = response.next_cursor;
+        }
+
+        Ok(comments)
+    }
+
+    /// 取得済みのブロックツリーをページ本文として変換する。ネットワークにはアクセスしない。
+    ///
+    /// `convert_page` から取得処理と frontmatter を除いたもので、コメントの脚注
+    /// （`include_comments`）と折り返し（`wrap_width`）も適用する。ブロックを自前で
+    /// 取得・キャッシュしている場合や、手で組み立てたツリーのテストに使う。
+    /// リンク先のタイトルやブックマークの Open Graph 情報など、取得時に解決する情報は
+    /// 使われない（URL などにフォールバックする）。
+    pub fn render_tree(&self, blocks: &[BlockWithChildren]) -> Result<String> {
+        let mut body = self.convert_blocks_to_markdown(blocks)?;
+        if self.config.include_comments {
+            body.push_str(&self.comments_to_footnotes(blocks));
+        }
+        if let Some(width) = self.config.wrap_width {
+            body = utils::wrap_markdown(&body, width);
+        }
+        Ok(body)
+    }
+
+    /// ブロック列を 1 つの文書として変換する。変換ごとに [`ConversionContext`] を作り、
+    /// 見出しのスラッグや同期ブロックの記録などはこの変換の中だけで数える
     pub fn convert_blocks_to_markdown(&self, blocks: &[BlockWithChildren]) -> Result<String> {
+        let conversion = self.conversion_context(blocks);
+        let mut result = self.convert_blocks_at_depth(blocks, 1, &conversion);
+        self.flush_unsupported(&conversion);
+        // 参照形式の画像の定義は文書の最後にまとめる
+        let refs = std::mem::take(&mut *conversion.image_refs.lock().unwrap());
+        if let (Ok(markdown), false) = (&mut result, refs.is_empty()) {
+            while !markdown.is_empty() && !markdown.ends_with("\n\n") {
+                markdown.push('\n');
+            }
+            for (i, url) in refs.iter().enumerate() {
+                markdown.push_str(&format!("[image-{}]: {}\n", i + 1, url));
+            }
+        }
+        result
+    }
+
+    /// `blocks` を変換するための [`ConversionContext`] を作る。必要なら収集パスを通す
+    pub fn conversion_context(&self, blocks: &[BlockWithChildren]) -> ConversionContext {
+        // two_pass でなくても、目次は後ろの見出しを参照するので目次があれば収集パスを通す
+        let needs_collection = self.config.two_pass
+            || self::blocks(blocks)
+                .any(|block| matches!(block.block_type, BlockType::TableOfContents { .. }));
+        ConversionContext {
+            document: if needs_collection {
+                DocumentContext::collect(blocks)
+            } else {
+                DocumentContext::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// 変換中の文書の入れ子の子ブロックを変換する。`depth` は `ListContext::depth` の初期値になる
+    pub fn convert_blocks_at_depth(
+        &self,
+        blocks: &[BlockWithChildren],
+        depth: usize,
+        conversion: &ConversionContext,
+    ) -> Result<String> {
         let mut markdown = String::new();
-        let mut list_context = ListContext::new();
-        let mut prev_block_type = None;
-
-        for block in blocks {
-            if let Some(prev_type) = &prev_block_type {
-                if !matches!(prev_type, &BlockType::NumberedListItem { .. })
-                    && matches!(&block.block.block_type, BlockType::NumberedListItem { .. })
-                {
-                    list_context = ListContext::new();
+        let mut list_context = ListContext::with_depth(depth);
+        let mut prev_block_type: Option<&BlockType> = None;
+
+        for (i, block) in blocks.iter().enumerate() {
+            let prev = i.checked_sub(1).and_then(|j| blocks.get(j));
+            let next = blocks.get(i + 1);
+
+            // 番号はリストの種類が変わるたびに振り直す（箇条書きの番号は list_marker のときだけ使う）
+            if let Some(prev_type) = prev_block_type {
+                let starts_list = match &block.block.block_type {
+                    BlockType::NumberedListItem { .. } => {
+                        !matches!(prev_type, BlockType::NumberedListItem { .. })
+                    }
+                    BlockType::BulletedListItem { .. } => {
+                        !matches!(prev_type, BlockType::BulletedListItem { .. })
+                    }
+                    _ => false,
+                };
+                if starts_list {
+                    list_context = ListContext::with_depth(depth);
                 }
             }
-            markdown.push_str(&self.convert_block_to_markdown_inner(block, &mut list_context)?);
-            prev_block_type = Some(block.block.block_type.clone());
+            let mut block_markdown = self.convert_block_to_markdown_inner(
+                block,
+                prev,
+                next,
+                &mut list_context,
+                conversion,
+            )?;
+            if !block.comments.is_empty() {
+                block_markdown = Self::append_footnote_refs(block, block_markdown);
+            }
+            markdown.push_str(&block_markdown);
+            // 入れ子のリストは各コンバータが空行を詰めるので、ここで空行が入るのは最上位だけ
+            if self.config.loose_lists && is_list_item(Some(block)) && is_list_item(next) {
+                markdown.push('\n');
+            }
+
+            // リスト内の区切り線は連番をリセットしない
+            let keeps_list = self.config.divider_spacing == DividerSpacing::ContextAware
+                && matches!(&block.block.block_type, BlockType::Divider { .. })
+                && is_list_item(prev)
+                && is_list_item(next);
+            if !keeps_list {
+                prev_block_type = Some(&block.block.block_type);
+            }
         }
 
         Ok(markdown)
     }
 
+    /// `trailing_newlines` に登録された種類なら、変換結果の末尾の改行の数をそろえる。
+    /// 何も出力しなかったブロックはそのまま
+    pub(crate) fn apply_trailing_newlines(&self, block_type: &str, markdown: String) -> String {
+        match self.config.trailing_newlines.get(block_type) {
+            Some(&count) if !markdown.is_empty() => {
+                format!("{}{}", markdown.trim_end_matches('\n'), "\n".repeat(count))
+            }
+            _ => markdown,
+        }
+    }
+
+    /// 変換できないブロックを `unsupported_log_level` で記録する。
+    /// `summarize_unsupported` のときは種類ごとに数えて、変換の最後に 1 行ずつ出力する
+    pub fn report_unsupported(&self, conversion: &ConversionContext, block_type: &BlockType) {
+        let Some(level) = self.config.unsupported_log_level else {
+            return;
+        };
+        if self.config.summarize_unsupported {
+            *conversion
+                .unsupported
+                .lock()
+                .unwrap()
+                .entry(block_type_name(block_type))
+                .or_default() += 1;
+        } else {
+            log::log!(level, "Unsupported block type: {:?}", block_type);
+        }
+    }
+
+    fn flush_unsupported(&self, conversion: &ConversionContext) {
+        let counts = std::mem::take(&mut *conversion.unsupported.lock().unwrap());
+        let Some(level) = self.config.unsupported_log_level else {
+            return;
+        };
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort();
+        for (name, count) in counts {
+            log::log!(level, "{} unsupported blocks of type {}", count, name);
+        }
+    }
+
+    // コメントの脚注参照をブロックの 1 行目の末尾に付ける。
+    // コードブロックやテーブルなど 1 行目に付けると壊れるものは直後の行に置く
+    fn append_footnote_refs(block: &BlockWithChildren, markdown: String) -> String {
+        let refs = block
+            .comments
+            .iter()
+            .map(|comment| format!("[^{}]", Self::footnote_label(comment)))
+            .collect::<String>();
+
+        let inline = matches!(
+            &block.block.block_type,
+            BlockType::Paragraph { .. }
+                | BlockType::Heading1 { .. }
+                | BlockType::Heading2 { .. }
+                | BlockType::Heading3 { .. }
+                | BlockType::BulletedListItem { .. }
+                | BlockType::NumberedListItem { .. }
+                | BlockType::ToDo { .. }
+                | BlockType::Toggle { .. }
+                | BlockType::Quote { .. }
+                | BlockType::Callout { .. }
+        );
+
+        match markdown.find('\n') {
+            Some(pos) if inline => format!("{}{}{}", &markdown[..pos], refs, &markdown[pos..]),
+            _ => format!("{}{}\n", markdown, refs),
+        }
+    }
+
+    // 先頭だけだと別のコメントと重なることがあるので、ID 全体をラベルにする
+    fn footnote_label(comment: &Comment) -> String {
+        comment.id.replace('-', "")
+    }
+
+    /// ブロックツリー内のコメントを Markdown の脚注定義にまとめる
+    pub fn comments_to_footnotes(&self, blocks: &[BlockWithChildren]) -> String {
+        fn collect<'a>(blocks: &'a [BlockWithChildren], out: &mut Vec<&'a Comment>) {
+            for block in blocks {
+                out.extend(block.comments.iter());
+                collect(&block.children, out);
+            }
+        }
+
+        let mut comments = Vec::new();
+        collect(blocks, &mut comments);
+        if comments.is_empty() {
+            return String::new();
+        }
+
+        let mut markdown = String::from("\n");
+        for comment in comments {
+            // 複数行のコメントは 2 行目以降を字下げして同じ脚注に続ける
+            let text = self
+                .convert_rich_text(&comment.rich_text)
+                .lines()
+                .collect::<Vec<_>>()
+                .join("\n    ");
+            markdown.push_str(&format!("[^{}]: {}\n", Self::footnote_label(comment), text));
+        }
+        markdown
+    }
+
+    /// 装飾やリンクを除いたプレーンテキスト
+    pub fn plain_text(rich_text: &[notion_client::objects::rich_text::RichText]) -> String {
+        rich_text
+            .iter()
+            .filter_map(|text| match text {
+                // API から取得したものには plain_text が入るが、自前で組み立てたものは content を使う
+                notion_client::objects::rich_text::RichText::Text {
+                    text,
+                    plain_text: None,
+                    ..
+                } => Some(text.content.clone()),
+                _ => text.plain_text(),
+            })
+            .collect()
+    }
+
+    /// リッチテキストを Markdown にする。クライアントも設定も要らない（既定の `Config` を使う）。
+    /// 下線・文字色やページリンクなどの設定を反映するなら [`Self::convert_rich_text`] か
+    /// [`Self::rich_text_to_markdown_with`] を使う
     pub fn rich_text_to_markdown(
         rich_text: &[notion_client::objects::rich_text::RichText],
+    ) -> String {
+        Self::rich_text_to_markdown_with(rich_text, &Config::default())
+    }
+
+    /// 変換設定（メンションの扱いなど）を反映してリッチテキストを変換する
+    pub fn convert_rich_text(
+        &self,
+        rich_text: &[notion_client::objects::rich_text::RichText],
+    ) -> String {
+        Self::rich_text_to_markdown_with(rich_text, &self.config)
+    }
+
+    /// 任意の `Config` でリッチテキストを Markdown にする（プロパティの値などインスタンスがない場合向け）
+    pub fn rich_text_to_markdown_with(
+        rich_text: &[notion_client::objects::rich_text::RichText],
+        config: &Config,
     ) -> String {
         if rich_text.is_empty() {
             return String::new();
         }
+        if config.output_format == OutputFormat::PlainText {
+            return Self::plain_text(rich_text);
+        }
 
         let mut markdown = String::new();
 
         for text in rich_text {
             let mut content = match text {
                 notion_client::objects::rich_text::RichText::Text {
-                    text, plain_text, ..
+                    text,
+                    plain_text,
+                    annotations,
+                    ..
                 } => {
-                    let text_content = plain_text
+                    let mut text_content = plain_text
                         .as_ref()
                         .map(|s| s.as_str())
-                        .unwrap_or(&text.content);
-                    if let Some(link) = &text.link {
-                        format!("[{}]({})", text_content, link.url)
-                    } else {
-                        text_content.to_string()
+                        .unwrap_or(&text.content)
+                        .to_string();
+                    // インラインコードの中はそのまま出力される
+                    if !annotations.as_ref().is_some_and(|a| a.code) {
+                        text_content = utils::escape_text(&text_content, config);
                     }
+                    Self::with_link(
+                        text_content,
+                        text.link.as_ref().map(|link| link.url.as_str()),
+                    )
                 }
-                notion_client::objects::rich_text::RichText::Mention { plain_text, .. } => {
-                    plain_text.clone()
+                notion_client::objects::rich_text::RichText::Mention {
+                    mention,
+                    plain_text,
+                    annotations,
+                    href,
+                } => {
+                    let plain_text = &if annotations.code {
+                        plain_text.clone()
+                    } else {
+                        utils::escape_text(plain_text, config)
+                    };
+                    match mention {
+                        Mention::TemplateMention { template_mention } => {
+                            Self::template_mention_to_markdown(template_mention, config)
+                        }
+                        Mention::Date { date } => match &config.date_format {
+                            Some(format) if annotations.code => {
+                                Self::date_mention_to_markdown(date, format)
+                            }
+                            Some(format) => utils::escape_text(
+                                &Self::date_mention_to_markdown(date, format),
+                                config,
+                            ),
+                            None => plain_text.clone(),
+                        },
+                        Mention::Page { page } => {
+                            match config.page_links.as_ref().and_then(|r| r.resolve(&page.id)) {
+                                Some(href) => utils::link(plain_text, &href),
+                                None => plain_text.clone(),
+                            }
+                        }
+                        // リンク先のタイトルをリンクの文字列にする。タイトルが空なら URL
+                        Mention::LinkMention { link_mention } => {
+                            match link_mention.href.as_ref().or(href.as_ref()) {
+                                Some(url) => {
+                                    let title = match link_mention.title.trim() {
+                                        "" => url.as_str(),
+                                        title => title,
+                                    };
+                                    let mut link =
+                                        utils::link(&utils::escape_text(title, config), url);
+                                    if let (true, Some(description)) = (
+                                        config.link_mention_descriptions,
+                                        link_mention
+                                            .description
+                                            .as_deref()
+                                            .filter(|d| !d.trim().is_empty()),
+                                    ) {
+                                        link.push_str(&format!(
+                                            " — {}",
+                                            utils::escape_text(description.trim(), config)
+                                        ));
+                                    }
+                                    link
+                                }
+                                None => plain_text.clone(),
+                            }
+                        }
+                        _ => plain_text.clone(),
+                    }
                 }
-                notion_client::objects::rich_text::RichText::Equation { plain_text, .. } => {
-                    plain_text.clone()
+                notion_client::objects::rich_text::RichText::Equation {
+                    equation,
+                    plain_text,
+                    href,
+                    ..
+                } => {
+                    // 式が空のときは plain_text を使い、どちらも空なら空の `$$` を出力しない
+                    let expression = match equation.expression.trim() {
+                        "" => plain_text.trim(),
+                        _ => equation.expression.as_str(),
+                    };
+                    if expression.is_empty() {
+                        String::new()
+                    } else {
+                        Self::with_link(utils::inline_equation(expression), href.as_deref())
+                    }
                 }
                 notion_client::objects::rich_text::RichText::None => String::new(),
             };
@@ -141,18 +1365,7 @@ impl NotionToMarkdown {
                 }
                 notion_client::objects::rich_text::RichText::None => None,
             } {
-                if annotations.bold {
-                    content = format!("**{}**", content);
-                }
-                if annotations.italic {
-                    content = format!("*{}*", content);
-                }
-                if annotations.strikethrough {
-                    content = format!("~~{}~~", content);
-                }
-                if annotations.code {
-                    content = format!("`{}`", content);
-                }
+                content = utils::apply_annotations(&content, &annotations, config);
             }
 
             markdown.push_str(&content);
@@ -161,6 +1374,105 @@ impl NotionToMarkdown {
         markdown
     }
 
+    // リンク付きのテキスト・数式はリンクで包む（装飾はその外側に付ける）
+    fn with_link(content: String, url: Option<&str>) -> String {
+        match url {
+            Some(url) => utils::link(&content, url),
+            None => content,
+        }
+    }
+
+    // notion-client は日付を UTC の日時として読むので、日付だけのメンションは UTC の 0 時になる。
+    // タイムゾーンの指定がなく 0 時ちょうどのものは日付として出力する
+    fn date_mention_to_markdown(date: &DatePropertyValue, format: &DateFormat) -> String {
+        let time_zone = date
+            .time_zone
+            .as_deref()
+            .and_then(|name| name.parse::<chrono_tz::Tz>().ok());
+        let render = |datetime: &DateTime<Utc>| match time_zone {
+            Some(tz) => datetime
+                .with_timezone(&tz)
+                .format(&format.datetime)
+                .to_string(),
+            None if datetime.num_seconds_from_midnight() == 0 => {
+                datetime.format(&format.date).to_string()
+            }
+            None => datetime.format(&format.datetime).to_string(),
+        };
+        match &date.end {
+            Some(end) => format!("{} → {}", render(&date.start), render(end)),
+            None => render(&date.start),
+        }
+    }
+
+    fn template_mention_to_markdown(mention: &TemplateMention, config: &Config) -> String {
+        match (&config.template_mention, mention) {
+            (
+                TemplateMentionStyle::Resolved,
+                TemplateMention::TemplateMentionDate {
+                    template_mention_date: TemplateMentionDate::Today,
+                },
+            ) => chrono::Local::now().format("%Y-%m-%d").to_string(),
+            (
+                TemplateMentionStyle::Resolved,
+                TemplateMention::TemplateMentionDate {
+                    template_mention_date: TemplateMentionDate::Now,
+                },
+            ) => chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            (
+                TemplateMentionStyle::Token { today, .. },
+                TemplateMention::TemplateMentionDate {
+                    template_mention_date: TemplateMentionDate::Today,
+                },
+            ) => today.clone(),
+            (
+                TemplateMentionStyle::Token { now, .. },
+                TemplateMention::TemplateMentionDate {
+                    template_mention_date: TemplateMentionDate::Now,
+                },
+            ) => now.clone(),
+            (_, TemplateMention::TemplateMentionUser { .. }) => "@me".to_string(),
+        }
+    }
+
+    /// 出力用のファイル URL を返す。
+    /// Notion にホストされたファイルの URL は 1 時間ほどで失効するため警告を出し、
+    /// `strict_expiring_urls` が有効ならエラーにする。`url_rewriter` があれば書き換えた URL を返し、
+    /// それがローカルのパスなら `asset_path_style` に従って区切り文字や空白をそろえる
+    pub fn file_url(&self, file: &File) -> Result<String> {
+        let original = Self::get_file_url(file);
+        let url = match &self.config.url_rewriter {
+            Some(rewriter) => rewriter.rewrite(&original),
+            None => original.clone(),
+        };
+        // 書き換え後も期限付きの URL のまま出力される場合だけエラー・警告にする
+        if let File::File { file } = file {
+            if url == original {
+                if self.config.strict_expiring_urls {
+                    anyhow::bail!(
+                        "Notion-hosted file URL expires at {}: {}",
+                        file.expiry_time,
+                        file.url
+                    );
+                }
+                log::warn!(
+                    "Emitting Notion-hosted file URL that expires at {}: {}",
+                    file.expiry_time,
+                    file.url
+                );
+            }
+        }
+        if self.config.url_rewriter.is_none() {
+            return Ok(url);
+        }
+        Ok(match &self.config.asset_path_style {
+            Some(style) if !url.contains("://") && !url.starts_with("data:") => {
+                utils::local_asset_path(&url, style)
+            }
+            _ => url,
+        })
+    }
+
     pub fn get_file_url(file: &File) -> String {
         match file {
             File::External { external } => external.url.clone(),
@@ -169,6 +1481,79 @@ impl NotionToMarkdown {
     }
 }
 
+// 権限不足（Notion API は 403 か、共有されていない場合は 404 を返す）による取得エラーかどうか
+fn is_inaccessible(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<NotionClientError>(),
+        Some(NotionClientError::InvalidStatusCode { error }) if error.status == 403 || error.status == 404
+    )
+}
+
+// データベースの列（プロパティ名）。タイトルの列が先頭、残りは名前順
+fn database_columns(page: &Page) -> Vec<String> {
+    let mut columns = page.properties.keys().cloned().collect::<Vec<_>>();
+    columns.sort_by_key(|name| {
+        (
+            !matches!(page.properties[name], PageProperty::Title { .. }),
+            name.clone(),
+        )
+    });
+    columns
+}
+
+// `TableOfContents { .. }` の Debug 表記からバリアント名だけを取り出す
+fn block_type_name(block_type: &BlockType) -> String {
+    let debug = format!("{:?}", block_type);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn is_rate_limited(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<NotionClientError>(),
+        Some(NotionClientError::InvalidStatusCode { error }) if error.status == 429
+    )
+}
+
+// 取得できなかった子ブロックの代わりに置く HTML コメント
+fn inaccessible_placeholder(block_id: &str) -> BlockWithChildren {
+    let comment = format!("<!-- notion2md: inaccessible block {} -->", block_id);
+    BlockWithChildren {
+        block: Block {
+            block_type: BlockType::Paragraph {
+                paragraph: ParagraphValue {
+                    rich_text: vec![RichText::Text {
+                        text: Text {
+                            content: comment,
+                            link: None,
+                        },
+                        annotations: None,
+                        plain_text: None,
+                        href: None,
+                    }],
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        },
+        children: Vec::new(),
+        comments: Vec::new(),
+    }
+}
+
+/// 箇条書き・番号付きリスト・TODO のいずれかのブロックかどうか
+pub fn is_list_item(block: Option<&BlockWithChildren>) -> bool {
+    matches!(
+        block.map(|b| &b.block.block_type),
+        Some(BlockType::BulletedListItem { .. })
+            | Some(BlockType::NumberedListItem { .. })
+            | Some(BlockType::ToDo { .. })
+    )
+}
+
 #[derive(Default)]
 pub struct ListContext {
     counters: Vec<usize>,
@@ -179,12 +1564,28 @@ impl ListContext {
         Self { counters: vec![0] }
     }
 
+    pub fn with_depth(depth: usize) -> Self {
+        Self {
+            counters: vec![0; depth.max(1)],
+        }
+    }
+
     pub fn next_number(&mut self) -> usize {
         let current_level = self.counters.len() - 1;
         self.counters[current_level] += 1;
         self.counters[current_level]
     }
 
+    /// 現在のリストの入れ子の深さ（最上位が 1）。
+    /// カスタムコンバータで子要素を自前でインデントするときに使う:
+    ///
+    /// ```ignore
+    /// let indent = "  ".repeat(payload.list_ctx.depth() - 1);
+    /// ```
+    pub fn depth(&self) -> usize {
+        self.counters.len()
+    }
+
     pub fn push(&mut self) {
         self.counters.push(0);
     }
@@ -196,3 +1597,65 @@ impl ListContext {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_context_depth_follows_push_and_pop() {
+        let mut ctx = ListContext::new();
+        assert_eq!(ctx.depth(), 1);
+
+        ctx.push();
+        ctx.push();
+        assert_eq!(ctx.depth(), 3);
+
+        ctx.pop();
+        assert_eq!(ctx.depth(), 2);
+
+        // 最上位より上には戻らない
+        ctx.pop();
+        ctx.pop();
+        assert_eq!(ctx.depth(), 1);
+    }
+
+    fn node(id: &str, children: Vec<BlockWithChildren>) -> BlockWithChildren {
+        BlockWithChildren {
+            block: Block {
+                id: Some(id.to_string()),
+                ..Default::default()
+            },
+            children,
+            comments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn blocks_are_traversed_depth_first() {
+        let tree = vec![
+            node(
+                "a",
+                vec![node("a1", vec![node("a1x", vec![])]), node("a2", vec![])],
+            ),
+            node("b", vec![]),
+            node("c", vec![node("c1", vec![])]),
+        ];
+        let ids = |iter: Blocks<'_>| iter.filter_map(|b| b.id.clone()).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(blocks(&tree)),
+            vec!["a", "a1", "a1x", "a2", "b", "c", "c1"]
+        );
+        assert_eq!(ids(tree[0].iter()), vec!["a", "a1", "a1x", "a2"]);
+        assert!(blocks(&[]).next().is_none());
+    }
+
+    #[test]
+    fn list_context_with_depth() {
+        let mut ctx = ListContext::with_depth(3);
+        assert_eq!(ctx.depth(), 3);
+        assert_eq!(ctx.next_number(), 1);
+        assert_eq!(ListContext::with_depth(0).depth(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{notion_to_md::NotionToMarkdown, utils};
+use notion_client::objects::page::{DateOrDateTime, PageProperty, RelationPropertyValue};
+
+/// テーブルのセルでチェックボックスのプロパティを表す記号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CheckboxStyle {
+    /// `✓` / `✗`
+    #[default]
+    Mark,
+    /// `[x]` / `[ ]`
+    Brackets,
+}
+
+impl CheckboxStyle {
+    pub fn render(&self, checked: bool) -> &'static str {
+        match (self, checked) {
+            (CheckboxStyle::Mark, true) => "✓",
+            (CheckboxStyle::Mark, false) => "✗",
+            (CheckboxStyle::Brackets, true) => "[x]",
+            (CheckboxStyle::Brackets, false) => "[ ]",
+        }
+    }
+}
+
+/// テーブルのセルや定義リストで multi_select の選択肢をつなぐ書き方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiSelectStyle {
+    /// `rust, notion`
+    #[default]
+    Comma,
+    /// `rust notion`
+    Space,
+    /// `#rust #notion`（名前の中の空白は `_` にする）
+    Hashtag,
+}
+
+impl MultiSelectStyle {
+    pub fn join(&self, names: &[&str]) -> String {
+        match self {
+            MultiSelectStyle::Comma => names.join(", "),
+            MultiSelectStyle::Space => names.join(" "),
+            MultiSelectStyle::Hashtag => names
+                .iter()
+                .map(|name| format!("#{}", name.split_whitespace().collect::<Vec<_>>().join("_")))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// データベースの行のプロパティを、テーブルのセルに入れる文字列にする。
+///
+/// * `checkbox` は `CheckboxStyle` に従って `✓`/`✗` か `[x]`/`[ ]` にする
+/// * `select` / `status` は選択肢の名前、`multi_select` は名前をカンマ区切りにする
+///   （[`property_to_cell_text_with`] で変えられる）
+/// * `relation` は関連ページへのリンクをカンマ区切りにする（リンクの文字列はページ ID）
+/// * 対応していない型は空文字列になる
+///
+/// `|` や改行のエスケープはしないので、Markdown テーブルでは `utils::table_cell` を通す
+pub fn property_to_cell_text(property: &PageProperty, checkbox_style: CheckboxStyle) -> String {
+    property_to_cell_text_with(property, checkbox_style, MultiSelectStyle::default())
+}
+
+/// [`property_to_cell_text`] に加えて、`multi_select` の選択肢のつなぎ方を指定する
+pub fn property_to_cell_text_with(
+    property: &PageProperty,
+    checkbox_style: CheckboxStyle,
+    multi_select_style: MultiSelectStyle,
+) -> String {
+    match property {
+        PageProperty::Title {
+            title: rich_text, ..
+        }
+        | PageProperty::RichText { rich_text, .. } => {
+            NotionToMarkdown::rich_text_to_markdown(rich_text)
+        }
+        PageProperty::Checkbox { checkbox, .. } => checkbox_style.render(*checkbox).to_string(),
+        PageProperty::Select { select, .. } | PageProperty::Status { status: select, .. } => select
+            .as_ref()
+            .and_then(|option| option.name.clone())
+            .unwrap_or_default(),
+        PageProperty::MultiSelect { multi_select, .. } => multi_select_style.join(
+            &multi_select
+                .iter()
+                .filter_map(|option| option.name.as_deref())
+                .collect::<Vec<_>>(),
+        ),
+        PageProperty::Number { number, .. } => {
+            number.as_ref().map(|n| n.to_string()).unwrap_or_default()
+        }
+        PageProperty::Date { date, .. } => date
+            .as_ref()
+            .and_then(|date| date.start.as_ref())
+            .map(|start| match start {
+                DateOrDateTime::Date(date) => date.to_string(),
+                DateOrDateTime::DateTime(datetime) => datetime.to_rfc3339(),
+            })
+            .unwrap_or_default(),
+        PageProperty::Url { url: value, .. }
+        | PageProperty::Email { email: value, .. }
+        | PageProperty::PhoneNumber {
+            phone_number: value,
+            ..
+        } => value.clone().unwrap_or_default(),
+        PageProperty::Relation { relation, .. } => relation_links(relation, |_| None),
+        _ => String::new(),
+    }
+}
+
+/// relation の関連ページを Notion のページへのリンクにし、カンマでつなぐ。
+/// リンクの文字列は `title_of` が返したタイトルで、`None` のときはページ ID
+pub fn relation_links<F>(relation: &[RelationPropertyValue], title_of: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    relation
+        .iter()
+        .map(|page| {
+            let title = title_of(&page.id).unwrap_or_else(|| page.id.clone());
+            utils::link(&title, &utils::notion_url(&page.id))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notion_client::objects::page::SelectPropertyValue;
+
+    fn checkbox(checked: bool) -> PageProperty {
+        PageProperty::Checkbox {
+            id: None,
+            checkbox: checked,
+        }
+    }
+
+    #[test]
+    fn checkbox_styles() {
+        assert_eq!(
+            property_to_cell_text(&checkbox(true), CheckboxStyle::Mark),
+            "✓"
+        );
+        assert_eq!(
+            property_to_cell_text(&checkbox(false), CheckboxStyle::Mark),
+            "✗"
+        );
+        assert_eq!(
+            property_to_cell_text(&checkbox(true), CheckboxStyle::Brackets),
+            "[x]"
+        );
+        assert_eq!(
+            property_to_cell_text(&checkbox(false), CheckboxStyle::Brackets),
+            "[ ]"
+        );
+    }
+
+    #[test]
+    fn select_uses_option_name() {
+        let select = PageProperty::Select {
+            id: None,
+            select: Some(SelectPropertyValue {
+                name: Some("進行中".to_string()),
+                ..Default::default()
+            }),
+        };
+        assert_eq!(
+            property_to_cell_text(&select, CheckboxStyle::Mark),
+            "進行中"
+        );
+
+        let empty = PageProperty::Select {
+            id: None,
+            select: None,
+        };
+        assert_eq!(property_to_cell_text(&empty, CheckboxStyle::Mark), "");
+    }
+
+    #[test]
+    fn multi_select_styles() {
+        let tags = PageProperty::MultiSelect {
+            id: None,
+            multi_select: ["rust", "static site"]
+                .into_iter()
+                .map(|name| SelectPropertyValue {
+                    name: Some(name.to_string()),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let text = |style| property_to_cell_text_with(&tags, CheckboxStyle::Mark, style);
+        assert_eq!(text(MultiSelectStyle::Comma), "rust, static site");
+        assert_eq!(text(MultiSelectStyle::Space), "rust static site");
+        assert_eq!(text(MultiSelectStyle::Hashtag), "#rust #static_site");
+    }
+
+    #[test]
+    fn relation_links_to_pages() {
+        let relation = PageProperty::Relation {
+            id: None,
+            relation: vec![RelationPropertyValue {
+                id: "0123-abcd".to_string(),
+            }],
+            has_more: None,
+        };
+        assert_eq!(
+            property_to_cell_text(&relation, CheckboxStyle::Mark),
+            "[0123-abcd](https://www.notion.so/0123abcd)"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use notion_client::{
+    endpoints::{
+        blocks::retrieve::response::RetrieveBlockChilerenResponse,
+        comments::retrieve::response::RetrieveCommentsResponse,
+        databases::query::{request::QueryDatabaseRequest, response::QueryDatabaseResponse},
+        Client,
+    },
+    objects::{
+        block::Block,
+        page::{Page, PageProperty},
+        parent::Parent,
+    },
+};
+
+use crate::{notion_to_md::NotionToMarkdown, utils};
+
+/// ブックマークのリンク先から取得した Open Graph の情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkMetadata {
+    pub title: String,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+/// リンクドデータベースのビューに設定されたフィルタ・並べ替え。
+/// 値はソースが用意した説明文で、そのままコメントに書き出す
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseView {
+    pub filter: Option<String>,
+    pub sorts: Vec<String>,
+}
+
+/// ブロックの子要素の取得元。
+/// 通常は Notion API クライアントだが、テストやキャッシュ用に差し替えられる。
+pub trait BlockSource: Send + Sync {
+    fn retrieve_block_children<'a>(
+        &'a self,
+        block_id: &'a str,
+        start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<RetrieveBlockChilerenResponse>>;
+
+    /// ブロック 1 つを取得する。`convert_block_ids` で使う
+    fn retrieve_block<'a>(&'a self, _block_id: &'a str) -> BoxFuture<'a, Result<Block>> {
+        Box::pin(async { anyhow::bail!("this block source cannot retrieve blocks") })
+    }
+
+    /// ブロックに付いたコメントを 1 ページ分取得する。`include_comments` が有効なときだけ呼ばれる
+    fn retrieve_comments<'a>(
+        &'a self,
+        _block_id: &'a str,
+        _start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<RetrieveCommentsResponse>> {
+        Box::pin(async {
+            Ok(RetrieveCommentsResponse {
+                object: "list".to_string(),
+                results: Vec::new(),
+                next_cursor: None,
+                has_more: false,
+            })
+        })
+    }
+
+    /// ページ本体（プロパティ・カバー画像など）を取得する。frontmatter やカバー画像の出力に使う
+    fn retrieve_page<'a>(&'a self, _page_id: &'a str) -> BoxFuture<'a, Result<Page>> {
+        Box::pin(async { anyhow::bail!("this block source cannot retrieve pages") })
+    }
+
+    /// ページの親を取得する。パンくずリストを作るために使う
+    fn retrieve_page_parent<'a>(
+        &'a self,
+        _page_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Parent>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// データベースの行（ページ）を 1 ページ分取得する
+    fn query_database<'a>(
+        &'a self,
+        _database_id: &'a str,
+        _start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<QueryDatabaseResponse>> {
+        Box::pin(async { anyhow::bail!("this block source cannot query databases") })
+    }
+
+    /// リンク先のページ・データベースのタイトルを取得する。解決できなければ `None`
+    fn retrieve_title<'a>(&'a self, _target: &'a Parent) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// ブックマーク・リンクプレビューのリンク先の Open Graph 情報を取得する。
+    /// `bookmark_as_card` / `link_preview_titles` が有効なときだけ呼ばれる
+    fn retrieve_link_metadata<'a>(
+        &'a self,
+        _url: &'a str,
+    ) -> BoxFuture<'a, Result<Option<LinkMetadata>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// ファイルのサイズ（バイト数）を取得する。Notion の API はファイルのサイズを返さないので、
+    /// API クライアントではリンク先に HEAD リクエストを送り `Content-Length` を読む。
+    /// `file_sizes` が有効なときだけ呼ばれる
+    fn retrieve_file_size<'a>(&'a self, _url: &'a str) -> BoxFuture<'a, Result<Option<u64>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// child_database ブロックのビューのフィルタ・並べ替えを取得する。
+    /// `database_view_comments` が有効なときだけ呼ばれる。Notion の公開 API はビューの設定を
+    /// 返さないため、API クライアントでは常に `None`（エクスポートなどから読むソース向け）
+    fn retrieve_database_view<'a>(
+        &'a self,
+        _block_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<DatabaseView>>> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// Notion API から取得するソース（既定の取得元）。
+/// ブックマークの Open Graph 情報やファイルサイズの取得には、ソースが持つ 1 つの HTTP クライアントを使い回す
+#[derive(Clone)]
+pub struct NotionApiSource {
+    client: Client,
+    http: notion_reqwest::Client,
+}
+
+impl NotionApiSource {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            http: notion_reqwest::Client::new(),
+        }
+    }
+}
+
+impl BlockSource for NotionApiSource {
+    fn retrieve_block_children<'a>(
+        &'a self,
+        block_id: &'a str,
+        start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<RetrieveBlockChilerenResponse>> {
+        Box::pin(async move {
+            Ok(self
+                .client
+                .blocks
+                .retrieve_block_children(block_id, start_cursor, None)
+                .await?)
+        })
+    }
+
+    fn retrieve_block<'a>(&'a self, block_id: &'a str) -> BoxFuture<'a, Result<Block>> {
+        Box::pin(async move { Ok(self.client.blocks.retrieve_a_block(block_id).await?) })
+    }
+
+    fn retrieve_comments<'a>(
+        &'a self,
+        block_id: &'a str,
+        start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<RetrieveCommentsResponse>> {
+        Box::pin(async move {
+            // notion-client 側のメソッド名は retrieve_a_user だが、中身はコメント取得
+            Ok(self
+                .client
+                .comments
+                .retrieve_a_user(block_id, start_cursor, None)
+                .await?)
+        })
+    }
+
+    fn retrieve_page<'a>(&'a self, page_id: &'a str) -> BoxFuture<'a, Result<Page>> {
+        Box::pin(async move { Ok(self.client.pages.retrieve_a_page(page_id, None).await?) })
+    }
+
+    fn retrieve_page_parent<'a>(
+        &'a self,
+        page_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Parent>>> {
+        Box::pin(async move {
+            let page = self.client.pages.retrieve_a_page(page_id, None).await?;
+            Ok(Some(page.parent))
+        })
+    }
+
+    fn query_database<'a>(
+        &'a self,
+        database_id: &'a str,
+        start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<QueryDatabaseResponse>> {
+        Box::pin(async move {
+            let request = QueryDatabaseRequest {
+                start_cursor: start_cursor.map(str::to_string),
+                ..Default::default()
+            };
+            Ok(self
+                .client
+                .databases
+                .query_a_database(database_id, request)
+                .await?)
+        })
+    }
+
+    fn retrieve_title<'a>(&'a self, target: &'a Parent) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            match target {
+                Parent::DatabaseId { database_id } => {
+                    let database = self
+                        .client
+                        .databases
+                        .retrieve_a_database(database_id)
+                        .await?;
+                    Ok(Some(NotionToMarkdown::plain_text(&database.title)))
+                }
+                Parent::PageId { page_id } => {
+                    let page = self.client.pages.retrieve_a_page(page_id, None).await?;
+                    Ok(page
+                        .properties
+                        .values()
+                        .find_map(|property| match property {
+                            PageProperty::Title { title, .. } => {
+                                Some(NotionToMarkdown::plain_text(title))
+                            }
+                            _ => None,
+                        }))
+                }
+                _ => Ok(None),
+            }
+        })
+    }
+
+    fn retrieve_link_metadata<'a>(
+        &'a self,
+        url: &'a str,
+    ) -> BoxFuture<'a, Result<Option<LinkMetadata>>> {
+        Box::pin(async move {
+            let html = self.http.get(url).send().await?.text().await?;
+            Ok(utils::open_graph(&html))
+        })
+    }
+
+    fn retrieve_file_size<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Option<u64>>> {
+        Box::pin(async move {
+            let response = self.http.head(url).send().await?.error_for_status()?;
+            // HEAD の応答は本文がないので content_length() ではなくヘッダーを読む
+            Ok(response
+                .headers()
+                .get(notion_reqwest::header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok()))
+        })
+    }
+}
@@ -14,13 +14,36 @@
 // src/lib.rs – Markdown utility helpers + equivalent tests
 // ------------------------------------------------------------
 
+#[cfg(feature = "image-embed")]
 use base64::{Engine as _, engine::general_purpose};
+use notion_client::objects::page::Color;
+use notion_client::objects::rich_text::{Annotations, RichText, TextColor};
 use regex::Regex;
-use std::error::Error;
+use std::{collections::HashMap, error::Error, sync::OnceLock};
+
+use crate::{
+    config::{
+        AnnotationKind, AssetPathStyle, Config, HtmlMode, LineEnding, OutputFormat, TableAlignment,
+    },
+    notion_to_md::{is_list_item, BlockWithChildren, NotionToMarkdown},
+    source::LinkMetadata,
+};
 
 /// Wrap text with back‑ticks – `inline code`.
+/// Content containing back‑ticks gets a delimiter one longer than its longest back‑tick run, and
+/// a space of padding when it starts or ends with one, as CommonMark requires: ``` ``a`b`` ```.
 pub fn inline_code(text: &str) -> String {
-    format!("`{}`", text)
+    let longest_run = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run + 1);
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{} {} {}", fence, text, fence)
+    } else {
+        format!("{}{}{}", fence, text, fence)
+    }
 }
 
 /// Wrap an inline equation with single `$` delimiters.
@@ -48,20 +71,339 @@ pub fn underline(text: &str) -> String {
     format!("<u>{}</u>", text)
 }
 
+/// Visible caption under an image or other media – `*caption*`.
+pub fn caption(text: &str) -> String {
+    format!("*{}*", text)
+}
+
 /// Hyperlink – `[text](href)`.
 pub fn link(text: &str, href: &str) -> String {
     format!("[{}]({})", text, href)
 }
 
+/// GFM autolink: `<https://example.com>`.
+pub fn autolink(url: &str) -> String {
+    format!("<{}>", url)
+}
+
+/// Convert a Notion rich-text array (e.g. from a property value) to Markdown without a client.
+/// Same as [`NotionToMarkdown::rich_text_to_markdown_with`].
+pub fn rich_text_to_markdown(rich_text: &[RichText], config: &Config) -> String {
+    NotionToMarkdown::rich_text_to_markdown_with(rich_text, config)
+}
+
+/// Wrap already-rendered rich text content with the Markdown for its Notion annotations.
+/// Underline and colour are HTML passthrough and only emitted when enabled in `config`.
+/// Empty or whitespace-only content is returned as is, since e.g. `****` breaks parsers.
+/// Wrappers nest in `config.annotation_order`, innermost first; the default is bold, italic,
+/// strikethrough, code, underline, colour — so underline + strikethrough is `<u>~~text~~</u>`.
+/// Underline and colour are HTML, so they follow `config.html_mode`: escaped tags with
+/// `Escaped`, nothing with `Stripped`.
+pub fn apply_annotations(content: &str, annotations: &Annotations, config: &Config) -> String {
+    let mut content = content.to_string();
+    if content.trim().is_empty() {
+        return content;
+    }
+    let mut order = config.annotation_order.clone();
+    for kind in AnnotationKind::DEFAULT_ORDER {
+        if !order.contains(&kind) {
+            order.push(kind);
+        }
+    }
+    let html = config.html_mode != HtmlMode::Stripped;
+    let tag = |wrapped: String, content: &str| match config.html_mode {
+        HtmlMode::Escaped if wrapped != content => escape_outer_tag(&wrapped),
+        _ => wrapped,
+    };
+    for kind in order {
+        content = match kind {
+            AnnotationKind::Bold if annotations.bold => format!("**{}**", content),
+            AnnotationKind::Italic if annotations.italic => format!("*{}*", content),
+            AnnotationKind::Strikethrough if annotations.strikethrough => strikethrough(&content),
+            AnnotationKind::Code if annotations.code => inline_code(&content),
+            AnnotationKind::Underline
+                if annotations.underline && config.render_underline && html =>
+            {
+                tag(underline(&content), &content)
+            }
+            AnnotationKind::Color if config.render_color && html => {
+                tag(color(&content, &annotations.color), &content)
+            }
+            _ => continue,
+        };
+    }
+    content
+}
+
+/// Escape the opening and closing tag of an element that wraps its content
+/// (`<u>text</u>` → `&lt;u>text&lt;/u>`), leaving the content itself as is.
+pub fn escape_outer_tag(html: &str) -> String {
+    match (html.find('>'), html.rfind('<')) {
+        (Some(open), Some(close)) if html.starts_with('<') && open < close => {
+            format!("&lt;{}&lt;{}", &html[1..close], &html[close + 1..])
+        }
+        _ => html.replace('<', "&lt;"),
+    }
+}
+
+/// Text / background colour using an inline `<span>`; the default colour is left untouched.
+pub fn color(text: &str, color: &TextColor) -> String {
+    if *color == TextColor::Default {
+        return text.to_string();
+    }
+    format!("<span style=\"{}\">{}</span>", color_style(color), text)
+}
+
+/// Width and height read from the header of a PNG, GIF, JPEG or WebP image without decoding
+/// it. Other formats and truncated headers give `None`.
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |i: usize| {
+        bytes
+            .get(i..i + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32)
+    };
+    let le16 = |i: usize| {
+        bytes
+            .get(i..i + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as u32)
+    };
+    let be32 = |i: usize| {
+        bytes
+            .get(i..i + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let le24 = |i: usize| {
+        bytes
+            .get(i..i + 3)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]))
+    };
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // IHDR is always the first chunk
+        return Some((be32(16)?, be32(20)?));
+    }
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        return Some((le16(6)?, le16(8)?));
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP".as_slice()) {
+        return match bytes.get(12..16)? {
+            b"VP8 " => Some((le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = le24(21)? | (*bytes.get(24)? as u32) << 24;
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        };
+    }
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        // Walk the segments up to the first start-of-frame marker
+        let mut i = 2;
+        while *bytes.get(i)? == 0xff {
+            let marker = *bytes.get(i + 1)?;
+            if marker == 0xff {
+                i += 1;
+                continue;
+            }
+            if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                return Some((be16(i + 7)?, be16(i + 5)?));
+            }
+            i += 2 + be16(i + 2)? as usize;
+        }
+    }
+    None
+}
+
+/// Converts every line break to `ending`. Existing `\r\n` are normalised first, so applying it
+/// twice is harmless.
+pub fn apply_line_ending(text: &str, ending: LineEnding) -> String {
+    let text = text.replace("\r\n", "\n");
+    match ending {
+        LineEnding::Lf => text,
+        LineEnding::CrLf => text.replace('\n', "\r\n"),
+    }
+}
+
+/// Select / status option as a badge: the option colour becomes the background of a `<span>`,
+/// the way Notion shows it. Options with the default colour are left untouched.
+pub fn badge(text: &str, option_color: &Color) -> String {
+    let background = match option_color {
+        Color::Default => TextColor::Default,
+        Color::Gray => TextColor::GrayBackground,
+        Color::Brown => TextColor::BrownBackground,
+        Color::Red => TextColor::RedBackground,
+        Color::Orange => TextColor::OrangeBackground,
+        Color::Yellow => TextColor::YellowBackground,
+        Color::Green => TextColor::GreenBackground,
+        Color::Blue => TextColor::BlueBackground,
+        Color::Purple => TextColor::PurpleBackground,
+        Color::Pink => TextColor::PinkBackground,
+    };
+    color(text, &background)
+}
+
+/// Block colours (`callout`, `quote`, ...) use their own enum in notion-client with the same
+/// variants as the rich-text one; converts it so the colour helpers can be shared.
+pub fn block_color(color: &notion_client::objects::block::TextColor) -> TextColor {
+    use notion_client::objects::block::TextColor as BlockColor;
+    match color {
+        BlockColor::Default => TextColor::Default,
+        BlockColor::Gray => TextColor::Gray,
+        BlockColor::GrayBackground => TextColor::GrayBackground,
+        BlockColor::Brown => TextColor::Brown,
+        BlockColor::BrownBackground => TextColor::BrownBackground,
+        BlockColor::Orange => TextColor::Orange,
+        BlockColor::OrangeBackground => TextColor::OrangeBackground,
+        BlockColor::Yellow => TextColor::Yellow,
+        BlockColor::YellowBackground => TextColor::YellowBackground,
+        BlockColor::Green => TextColor::Green,
+        BlockColor::GreenBackground => TextColor::GreenBackground,
+        BlockColor::Blue => TextColor::Blue,
+        BlockColor::BlueBackground => TextColor::BlueBackground,
+        BlockColor::Purple => TextColor::Purple,
+        BlockColor::PurpleBackground => TextColor::PurpleBackground,
+        BlockColor::Pink => TextColor::Pink,
+        BlockColor::PinkBackground => TextColor::PinkBackground,
+        BlockColor::Red => TextColor::Red,
+        BlockColor::RedBackground => TextColor::RedBackground,
+    }
+}
+
+/// Block-level counterpart of [`color`]: wraps Markdown in a styled `<div>`, with blank lines so
+/// the content is still parsed as Markdown.
+pub fn color_div(body: &str, color: &TextColor) -> String {
+    if *color == TextColor::Default {
+        return body.to_string();
+    }
+    format!(
+        "<div style=\"{}\">\n\n{}\n\n</div>\n\n",
+        color_style(color),
+        body.trim_end()
+    )
+}
+
+/// Notion colour name as in the API (`blue`, `yellow_background`).
+pub fn color_name(color: &TextColor) -> String {
+    let name = format!("{:?}", color).to_lowercase();
+    match name.strip_suffix("background") {
+        Some(background) => format!("{}_background", background),
+        None => name,
+    }
+}
+
+// `color: blue` / `background-color: yellow`
+fn color_style(color: &TextColor) -> String {
+    let name = format!("{:?}", color).to_lowercase();
+    match name.strip_suffix("background") {
+        Some(background) => format!("background-color: {}", background),
+        None => format!("color: {}", name),
+    }
+}
+
+/// URL of a Notion page or database from its id (dashes are optional).
+pub fn notion_url(id: &str) -> String {
+    format!("https://www.notion.so/{}", id.replace('-', ""))
+}
+
+/// Canonical form of a Notion id: the lowercase, dashed 8-4-4-4-12 UUID. Dashed and undashed
+/// input normalize to the same string; anything that is not 32 hex digits is only trimmed.
+pub fn normalize_notion_id(id: &str) -> String {
+    let id = id.trim();
+    let hex = id.replace('-', "").to_ascii_lowercase();
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return id.to_string();
+    }
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Page id (32 lowercase hex digits, no dashes) from a Notion URL such as
+/// `https://www.notion.so/ws/Title-0123…cdef?v=…`. The id is the trailing 32 hex digits of the last
+/// path segment, dashed or not; a `p=` query parameter (a page opened as a peek) takes precedence
+/// and other query parameters such as a database view's `v=` are ignored. A bare id is accepted.
+pub fn extract_page_id(url: &str) -> Option<String> {
+    let trailing_id = |text: &str| -> Option<String> {
+        let hex = text.replace('-', "");
+        let id = hex.get(hex.len().checked_sub(32)?..)?;
+        id.chars()
+            .all(|c| c.is_ascii_hexdigit())
+            .then(|| id.to_ascii_lowercase())
+    };
+
+    let url = url.trim();
+    let url = url.split('#').next().unwrap_or_default();
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let peek = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("p="))
+        .and_then(trailing_id);
+    peek.or_else(|| trailing_id(path.trim_end_matches('/').rsplit('/').next()?))
+}
+
+/// Local asset path as written into Markdown: both `/` and `\` become `style.separator`, and
+/// spaces become `%20` when `style.encode_spaces` is set.
+pub fn local_asset_path(path: &str, style: &AssetPathStyle) -> String {
+    let path = path.replace(['/', '\\'], &style.separator.to_string());
+    if style.encode_spaces {
+        path.replace(' ', "%20")
+    } else {
+        path
+    }
+}
+
 /// Fenced code‑block with an optional language identifier. Defaults to `plaintext`.
 pub fn code_block(text: &str, language: Option<&str>) -> String {
-    let lang = language
+    code_block_with_fence(text, language, '`')
+}
+
+/// Same as [`code_block`] but with a configurable fence character (`` ` `` or `~`).
+/// The fence is made one longer than the longest run of that character in the content,
+/// so code that itself contains a fence cannot close the block early.
+pub fn code_block_with_fence(text: &str, language: Option<&str>, fence_char: char) -> String {
+    code_block_with_options(text, language, fence_char, false)
+}
+
+/// Same as [`code_block_with_fence`]; with `omit_plaintext` a missing or `plaintext` language
+/// produces a bare fence instead of a `plaintext` label.
+pub fn code_block_with_options(
+    text: &str,
+    language: Option<&str>,
+    fence_char: char,
+    omit_plaintext: bool,
+) -> String {
+    let mut lang = language
         .map(|l| l.trim())
         .filter(|l| !l.is_empty())
         .unwrap_or("plaintext")
         .to_lowercase();
+    if omit_plaintext && lang == "plaintext" {
+        lang.clear();
+    }
+    let fence = fence_char
+        .to_string()
+        .repeat((longest_run(text, fence_char) + 1).max(3));
 
-    format!("```{}\n{}\n```", lang, text.trim())
+    format!("{}{}\n{}\n{}", fence, lang, text.trim(), fence)
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for ch in text.chars() {
+        if ch == c {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
 }
 
 /// Display equation block (double‑dollar fenced).
@@ -69,6 +411,18 @@ pub fn equation(text: &str) -> String {
     format!("$$\n{}\n$$", text.trim())
 }
 
+/// Display equation on a single line (`$$E = mc^2$$`), for places where every line gets a
+/// prefix such as `> ` and a multi-line `$$` fence would break.
+pub fn single_line_equation(text: &str) -> String {
+    let expression = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("$${}$$", expression)
+}
+
 /// Heading helpers.
 pub fn heading1(text: &str) -> String {
     format!("# {}", text)
@@ -80,11 +434,119 @@ pub fn heading3(text: &str) -> String {
     format!("### {}", text)
 }
 
+/// Anchor id for a heading: lowercased, whitespace becomes `-`, and anything other than
+/// letters, digits, `-` and `_` is dropped (`"What's new?"` → `whats-new`).
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().to_lowercase().chars() {
+        if c.is_alphanumeric() || c == '_' {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-') && !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Stateful [`slugify`] that keeps ids unique within a document: repeats get `-1`, `-2`, …
+/// (skipping suffixes that are already taken). Empty slugs are returned as-is and not recorded.
+#[derive(Debug, Default)]
+pub struct Slugger {
+    seen: HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = slugify(text);
+        if base.is_empty() {
+            return base;
+        }
+
+        let mut n = self.seen.get(&base).copied().unwrap_or(0);
+        let mut candidate = base.clone();
+        if n > 0 {
+            candidate = format!("{}-{}", base, n);
+            while self.seen.contains_key(&candidate) {
+                n += 1;
+                candidate = format!("{}-{}", base, n);
+            }
+        }
+        self.seen.insert(base, n + 1);
+        self.seen.entry(candidate.clone()).or_insert(1);
+        candidate
+    }
+
+    /// Forget every slug handed out so far (e.g. when starting a new page).
+    pub fn reset(&mut self) {
+        self.seen.clear();
+    }
+}
+
+/// JSX component for MDX output. Attribute values are HTML-escaped; an empty body gives a
+/// self-closing tag, otherwise the Markdown body is separated from the tags by blank lines.
+pub fn jsx_component(name: &str, attrs: &[(&str, &str)], body: &str) -> String {
+    let attrs = attrs
+        .iter()
+        .map(|(key, value)| format!(" {}=\"{}\"", key, html_escape(value)))
+        .collect::<String>();
+    if body.is_empty() {
+        format!("<{}{} />\n\n", name, attrs)
+    } else {
+        format!("<{}{}>\n\n{}\n</{}>\n\n", name, attrs, body, name)
+    }
+}
+
+/// Escape plain rich-text content for the configured output: MDX escapes `<`, `{` and `}`;
+/// Markdown (and GFM) escapes `<` as `&lt;` only when `config.escape_html` is set.
+pub fn escape_text(text: &str, config: &Config) -> String {
+    match config.output_format {
+        OutputFormat::Mdx => mdx_escape(text),
+        OutputFormat::Markdown | OutputFormat::Gfm if config.escape_html => {
+            text.replace('<', "&lt;")
+        }
+        OutputFormat::Markdown | OutputFormat::Gfm | OutputFormat::PlainText => text.to_string(),
+    }
+}
+
+/// Escape text that MDX would otherwise parse as JSX or an expression (`<`, `{`, `}`).
+pub fn mdx_escape(text: &str) -> String {
+    text.replace('{', "\\{")
+        .replace('}', "\\}")
+        .replace('<', "\\<")
+}
+
 /// Blockquote – handles multi‑line strings.
 pub fn quote(text: &str) -> String {
     format!("> {}", text)
 }
 
+/// Blockquote block shared by quotes and call‑outs: every line of `head` and then of `body`
+/// is quoted, followed by a blank line. Blank lines inside `body` stay quoted so that HTML
+/// blocks such as `<details>` keep their Markdown content inside the quote; trailing blank
+/// lines are dropped so the quote does not end with an empty `> ` line.
+pub fn blockquote(head: &str, body: &str) -> String {
+    let mut out = head
+        .lines()
+        .map(|line| format!("{}\n", quote(line)))
+        .collect::<String>();
+    let body = body
+        .trim_end_matches('\n')
+        .lines()
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !body.is_empty() {
+        out.push_str(&body);
+        out.push('\n');
+    }
+    out.push('\n');
+    out
+}
+
 /// Representation of a call‑out icon.
 #[derive(Debug, Clone)]
 pub enum CalloutIcon {
@@ -125,13 +587,60 @@ pub fn callout(text: &str, icon: Option<CalloutIcon>) -> String {
     format!("> {}{}", emoji_prefix, formatted_text)
 }
 
+/// Map a call‑out emoji to a Pandoc div class (falls back to `note`).
+pub fn callout_class(emoji: Option<&str>) -> &'static str {
+    match emoji.map(|e| e.trim_end_matches('\u{fe0f}')) {
+        Some("💡") => "tip",
+        Some("⚠") => "warning",
+        Some("❗") | Some("‼") => "important",
+        Some("🚨") | Some("❌") | Some("⛔") => "caution",
+        _ => "note",
+    }
+}
+
+/// Pandoc fenced div. The fence grows when the body already contains fenced divs.
+pub fn pandoc_div(class: &str, body: &str) -> String {
+    let inner = body
+        .lines()
+        .map(|line| line.trim_start().chars().take_while(|&c| c == ':').count())
+        .filter(|&n| n >= 3)
+        .max()
+        .unwrap_or(0);
+    let fence = ":".repeat((inner + 1).max(3));
+    format!("{} {}\n{}{}\n\n", fence, class, body, fence)
+}
+
 /// Unordered / ordered list helpers.
+///
+/// Continuation lines (soft line breaks inside the item) are aligned under the item text, so
+/// `10. ` gets four spaces of continuation indent while `- ` gets two.
 pub fn bullet(text: &str, count: Option<usize>) -> String {
+    let marker = match count {
+        Some(n) => format!("{}.", n),
+        None => "-".to_string(),
+    };
+    list_item(text, &marker)
+}
+
+/// List item with an arbitrary marker (`a.`, `iv.`); continuation lines are aligned with the
+/// text after the marker.
+pub fn list_item(text: &str, marker: &str) -> String {
     let trimmed = text.trim();
-    match count {
-        Some(n) => format!("{}. {}", n, trimmed),
-        None => format!("- {}", trimmed),
-    }
+    let marker = format!("{} ", marker);
+    let indent = " ".repeat(marker.chars().count());
+    let body = trimmed
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.is_empty() {
+                line.to_string()
+            } else {
+                format!("{}{}", indent, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{}{}", marker, body)
 }
 
 /// Task‑list item.
@@ -161,6 +670,26 @@ pub fn add_tab_space(text: &str, n: usize) -> String {
     out
 }
 
+/// Splits sibling blocks into runs: consecutive list items of the same kind (bulleted,
+/// numbered or to-do) form one run, and every other block is a run of its own.
+pub fn group_list_items(blocks: &[BlockWithChildren]) -> Vec<&[BlockWithChildren]> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for i in 1..=blocks.len() {
+        let same_list = blocks.get(i).is_some_and(|block| {
+            let prev = &blocks[i - 1];
+            is_list_item(Some(block))
+                && std::mem::discriminant(&block.block.block_type)
+                    == std::mem::discriminant(&prev.block.block_type)
+        });
+        if !same_list {
+            groups.push(&blocks[start..i]);
+            start = i;
+        }
+    }
+    groups
+}
+
 /// Horizontal rule.
 pub fn divider() -> &'static str {
     "---"
@@ -179,6 +708,24 @@ pub fn toggle(summary: Option<&str>, children: Option<&str>) -> String {
     }
 }
 
+/// Make already-rendered cell content safe inside a Markdown table row.
+/// Annotations must be applied first so that `**a|b**` becomes `**a\|b**`.
+pub fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+/// Swaps rows and columns. Short rows are padded with empty cells.
+pub fn transpose(rows: &[Vec<String>]) -> Vec<Vec<String>> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    (0..columns)
+        .map(|column| {
+            rows.iter()
+                .map(|row| row.get(column).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect()
+}
+
 /// Simple Markdown table generator.
 /// Pads each column to the width of the longest cell – sufficient for unit‑test purposes.
 pub fn table(rows: &[Vec<&str>]) -> String {
@@ -221,10 +768,220 @@ pub fn table(rows: &[Vec<&str>]) -> String {
     out.join("\n")
 }
 
+/// Markdown table with per-column alignment (`:---`, `:---:`, `---:`); columns without an entry
+/// in `alignments` get a plain `---`. With `pad`, cells are padded to the column width (at
+/// least 3) in the direction of their alignment; without it every cell is written as is.
+pub fn aligned_table(rows: &[Vec<&str>], alignments: &[TableAlignment], pad: bool) -> String {
+    let cols = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let alignment = |i: usize| alignments.get(i).copied().unwrap_or_default();
+    let mut col_widths = vec![3usize; cols];
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            col_widths[i] = col_widths[i].max(cell.chars().count());
+        }
+    }
+    let width = |i: usize| if pad { col_widths[i] } else { 3 };
+
+    let fmt_row = |row: &[&str]| -> String {
+        let cells = (0..cols)
+            .map(|i| {
+                let cell = row.get(i).copied().unwrap_or_default();
+                if !pad {
+                    return format!(" {} ", cell);
+                }
+                let space = col_widths[i] - cell.chars().count();
+                let (left, right) = match alignment(i) {
+                    TableAlignment::Right => (space, 0),
+                    TableAlignment::Center => (space / 2, space - space / 2),
+                    TableAlignment::None | TableAlignment::Left => (0, space),
+                };
+                format!(" {}{}{} ", " ".repeat(left), cell, " ".repeat(right))
+            })
+            .collect::<Vec<_>>();
+        format!("|{}|", cells.join("|"))
+    };
+
+    let separator = (0..cols)
+        .map(|i| {
+            let w = width(i);
+            let dashes = match alignment(i) {
+                TableAlignment::None => "-".repeat(w),
+                TableAlignment::Left => format!(":{}", "-".repeat(w - 1)),
+                TableAlignment::Right => format!("{}:", "-".repeat(w - 1)),
+                TableAlignment::Center => format!(":{}:", "-".repeat(w - 2)),
+            };
+            format!(" {} ", dashes)
+        })
+        .collect::<Vec<_>>();
+
+    let mut out = Vec::new();
+    if let Some((header, body)) = rows.split_first() {
+        out.push(fmt_row(header));
+        out.push(format!("|{}|", separator.join("|")));
+        out.extend(body.iter().map(|row| fmt_row(row)));
+    }
+    out.join("\n")
+}
+
+/// One CSV record (RFC 4180) terminated by `\n`. Fields containing a comma, quote or line
+/// break are quoted, with inner quotes doubled.
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("{}\n", fields.join(","))
+}
+
+/// HTML `<table>` for content that a Markdown table cannot hold.
+/// Multi-line cells become `<br>`-separated, and cells made only of `- ` items become `<ul>`.
+pub fn html_table(rows: &[Vec<String>], column_header: bool, row_header: bool) -> String {
+    let mut out = vec!["<table>".to_string()];
+
+    for (i, row) in rows.iter().enumerate() {
+        let header_row = column_header && i == 0;
+        let cells = row
+            .iter()
+            .enumerate()
+            .map(|(j, cell)| {
+                let tag = if header_row || (row_header && j == 0) {
+                    "th"
+                } else {
+                    "td"
+                };
+                format!("<{}>{}</{}>", tag, html_table_cell(cell), tag)
+            })
+            .collect::<String>();
+        out.push(format!("<tr>{}</tr>", cells));
+    }
+
+    out.push("</table>".to_string());
+    out.join("\n")
+}
+
+fn html_table_cell(text: &str) -> String {
+    let lines = text.lines().collect::<Vec<_>>();
+    if !lines.is_empty() && lines.iter().all(|line| line.starts_with("- ")) {
+        let items = lines
+            .iter()
+            .map(|line| format!("<li>{}</li>", &line[2..]))
+            .collect::<String>();
+        return format!("<ul>{}</ul>", items);
+    }
+    lines.join("<br>")
+}
+
+/// Escape text for use inside HTML element content or a quoted attribute.
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Extract Open Graph metadata (`og:title`, `og:description`, `og:image`) from an HTML page.
+/// Falls back to `<title>` when `og:title` is missing; returns `None` when no title is found.
+pub fn open_graph(html: &str) -> Option<LinkMetadata> {
+    static META: OnceLock<Regex> = OnceLock::new();
+    static CONTENT: OnceLock<Regex> = OnceLock::new();
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let meta_re = META.get_or_init(|| {
+        Regex::new(
+            r#"(?is)<meta\s+[^>]*(?:property|name)\s*=\s*["']og:(title|description|image)["'][^>]*>"#,
+        )
+        .unwrap()
+    });
+    let content_re = CONTENT
+        .get_or_init(|| Regex::new(r#"(?is)content\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+    let meta = |property: &str| {
+        meta_re
+            .captures_iter(html)
+            .find(|caps| caps[1].eq_ignore_ascii_case(property))
+            .and_then(|caps| content_re.captures(caps.get(0).unwrap().as_str()))
+            .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
+            .map(|value| html_unescape(value.as_str().trim()))
+            .filter(|value| !value.is_empty())
+    };
+    let title = meta("title").or_else(|| {
+        TITLE
+            .get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap())
+            .captures(html)
+            .map(|caps| html_unescape(caps[1].trim()))
+            .filter(|value| !value.is_empty())
+    })?;
+
+    Some(LinkMetadata {
+        title,
+        description: meta("description"),
+        image: meta("image"),
+    })
+}
+
+/// HTML link card for a bookmark: title, optional description and thumbnail inside an `<a>`.
+pub fn link_card(url: &str, metadata: &LinkMetadata) -> String {
+    let mut out = vec![format!(
+        "<a href=\"{}\" class=\"bookmark-card\">",
+        html_escape(url)
+    )];
+    if let Some(image) = &metadata.image {
+        out.push(format!("<img src=\"{}\" alt=\"\">", html_escape(image)));
+    }
+    out.push(format!("<strong>{}</strong>", html_escape(&metadata.title)));
+    if let Some(description) = &metadata.description {
+        out.push(format!("<p>{}</p>", html_escape(description)));
+    }
+    out.push("</a>".to_string());
+    out.join("\n")
+}
+
+/// Human-readable size in binary units with one decimal (`1.2 MB`); below 1 KB, whole bytes.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Last path segment of a URL, ignoring the query string and fragment (`.../photo.jpg?x=1` → `photo.jpg`).
+pub fn file_name_from_url(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| {
+        rest.split_once('/').map_or("", |(_, path)| path)
+    });
+    path.rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
 /// Helper that converts an image URL to Markdown, optionally embedding as base64.
 /// Follows the behaviour of the original JS implementation.
 /// * If `convert_to_base64` is false, or the href already contains a `data:` URI, we simply emit it.
 /// * Otherwise we synchronously download the image and embed the base64 payload (PNG‑assumed).
+///
+/// Downloading requires the `image-embed` feature; without it the plain URL is always emitted.
 pub fn image(alt: &str, href: &str, convert_to_base64: bool) -> Result<String, Box<dyn Error>> {
     if !convert_to_base64 || href.starts_with("data:") {
         if href.starts_with("data:") {
@@ -235,12 +992,188 @@ pub fn image(alt: &str, href: &str, convert_to_base64: bool) -> Result<String, B
         return Ok(format!("![{}]({})", alt, href));
     }
 
+    embed_image(alt, href)
+}
+
+#[cfg(feature = "image-embed")]
+fn embed_image(alt: &str, href: &str) -> Result<String, Box<dyn Error>> {
     // Blocking download
     let bytes = reqwest::blocking::get(href)?.bytes()?;
     let encoded = general_purpose::STANDARD.encode(bytes);
     Ok(format!("![{}](data:image/png;base64,{})", alt, encoded))
 }
 
+#[cfg(not(feature = "image-embed"))]
+fn embed_image(alt: &str, href: &str) -> Result<String, Box<dyn Error>> {
+    Ok(format!("![{}]({})", alt, href))
+}
+
+/// Re-flow Markdown so that lines fit in `width` columns where possible.
+/// * Fenced code blocks, tables, headings, HTML and display equations are left untouched.
+/// * Links, images and inline code spans are never split across lines.
+/// * Continuation lines keep the quote marker / list indentation of the original line.
+pub fn wrap_markdown(text: &str, width: usize) -> String {
+    let mut out = Vec::new();
+    let mut fence: Option<String> = None;
+
+    for line in text.split('\n') {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence_marker(trimmed) {
+            // A closing fence must be at least as long as the opening one and carry no info string.
+            let closes = fence
+                .as_deref()
+                .is_some_and(|open| marker.starts_with(open) && trimmed == marker);
+            if fence.is_none() {
+                fence = Some(marker);
+            } else if closes {
+                fence = None;
+            }
+            out.push(line.to_string());
+            continue;
+        }
+        if fence.is_some() || line.chars().count() <= width || is_block_syntax(trimmed) {
+            out.push(line.to_string());
+            continue;
+        }
+        out.extend(wrap_line(line, width));
+    }
+
+    out.join("\n")
+}
+
+/// Returns the run of backticks / tildes that opens or closes a code fence, if any.
+fn fence_marker(trimmed: &str) -> Option<String> {
+    let c = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let marker = trimmed
+        .chars()
+        .take_while(|ch| *ch == c)
+        .collect::<String>();
+    (marker.chars().count() >= 3).then_some(marker)
+}
+
+fn is_block_syntax(trimmed: &str) -> bool {
+    trimmed.starts_with('|')
+        || trimmed.starts_with('#')
+        || trimmed.starts_with('<')
+        || trimmed.starts_with("$$")
+        || trimmed.starts_with("[^")
+}
+
+/// Splits a line into its block prefix and the continuation prefix used for wrapped lines.
+fn line_prefix(line: &str) -> (String, String) {
+    static PREFIX: OnceLock<Regex> = OnceLock::new();
+    let re = PREFIX
+        .get_or_init(|| Regex::new(r"^(\s*)((?:> ?)*)([-*+] (?:\[[ xX]\] )?|\d+\. )?").unwrap());
+    let caps = re.captures(line).unwrap();
+    let indent = caps.get(1).map_or("", |m| m.as_str());
+    let quote = caps.get(2).map_or("", |m| m.as_str());
+    let marker = caps.get(3).map_or("", |m| m.as_str());
+
+    (
+        format!("{}{}{}", indent, quote, marker),
+        format!("{}{}{}", indent, quote, " ".repeat(marker.chars().count())),
+    )
+}
+
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let (first, continuation) = line_prefix(line);
+    let mut lines = Vec::new();
+    let mut current = first.clone();
+    let mut has_atom = false;
+
+    for atom in split_inline_atoms(&line[first.len()..]) {
+        let len = current.chars().count() + 1 + atom.chars().count();
+        // A wrapped line must not start with something that reads as block syntax.
+        if has_atom && len > width && !starts_block(&atom) {
+            lines.push(current);
+            current = continuation.clone();
+            has_atom = false;
+        }
+        if has_atom {
+            current.push(' ');
+        }
+        current.push_str(&atom);
+        has_atom = true;
+    }
+    lines.push(current);
+    lines
+}
+
+fn starts_block(atom: &str) -> bool {
+    static BLOCK_START: OnceLock<Regex> = OnceLock::new();
+    BLOCK_START
+        .get_or_init(|| Regex::new(r"^(?:[-*+>#|]|\d+[.)]$)").unwrap())
+        .is_match(atom)
+}
+
+/// Splits inline Markdown on spaces, keeping links and code spans as single atoms.
+fn split_inline_atoms(text: &str) -> Vec<String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut atoms = Vec::new();
+    let mut current = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let end = match chars[i] {
+            '`' => Some(code_span_end(&chars, i)),
+            '[' => link_end(&chars, i),
+            _ => None,
+        };
+
+        match (chars[i], end) {
+            (_, Some(end)) => {
+                current.extend(&chars[i..=end]);
+                i = end + 1;
+            }
+            (' ', None) => {
+                if !current.is_empty() {
+                    atoms.push(std::mem::take(&mut current));
+                }
+                i += 1;
+            }
+            (c, None) => {
+                current.push(c);
+                i += 1;
+            }
+        }
+    }
+    if !current.is_empty() {
+        atoms.push(current);
+    }
+    atoms
+}
+
+/// Returns the index of the last back‑tick of the code span opened by the back‑tick run at
+/// `start`. The span closes at the next run of the same length; without one, the opening run is
+/// literal text and its own end is returned.
+fn code_span_end(chars: &[char], start: usize) -> usize {
+    let run_len = |from: usize| chars[from..].iter().take_while(|&&c| c == '`').count();
+    let open = run_len(start);
+    let mut i = start + open;
+    while i < chars.len() {
+        if chars[i] == '`' {
+            let run = run_len(i);
+            if run == open {
+                return i + run - 1;
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    start + open - 1
+}
+
+/// Returns the index of the closing `)` of a `[text](href)` link starting at `start`.
+fn link_end(chars: &[char], start: usize) -> Option<usize> {
+    let close = start + chars[start..].iter().position(|&c| c == ']')?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let paren = close + 1 + chars[close + 1..].iter().position(|&c| c == ')')?;
+    Some(paren)
+}
+
 // ------------------------------------------------------------
 //                               Tests
 // ------------------------------------------------------------
@@ -268,6 +1201,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pandoc_div_classes_and_nesting() {
+        assert_eq!(callout_class(Some("⚠️")), "warning");
+        assert_eq!(callout_class(Some("😍")), "note");
+        assert_eq!(pandoc_div("tip", "text\n"), "::: tip\ntext\n:::\n\n");
+        assert_eq!(
+            pandoc_div("note", "::: tip\nx\n:::\n"),
+            ":::: note\n::: tip\nx\n:::\n::::\n\n"
+        );
+    }
+
+    #[test]
+    fn blockquote_quotes_head_and_body() {
+        assert_eq!(
+            blockquote("[!note] Title", "first\nsecond\n"),
+            "> [!note] Title\n> first\n> second\n\n"
+        );
+        assert_eq!(blockquote("one\ntwo", ""), "> one\n> two\n\n");
+        assert_eq!(blockquote("head", "a\n\nb\n\n"), "> head\n> a\n> \n> b\n\n");
+    }
+
+    #[test]
+    fn slugify_strips_punctuation() {
+        assert_eq!(slugify("What's new?"), "whats-new");
+        assert_eq!(slugify("  Hello,   World! -- 2024 "), "hello-world-2024");
+        assert_eq!(slugify("snake_case"), "snake_case");
+        assert_eq!(slugify("!!!"), "");
+    }
+
+    #[test]
+    fn slugify_keeps_unicode_letters() {
+        assert_eq!(slugify("日本語 の 見出し"), "日本語-の-見出し");
+        assert_eq!(slugify("Café Crème"), "café-crème");
+    }
+
+    #[test]
+    fn slugger_disambiguates_repeats() {
+        let mut slugger = Slugger::new();
+        assert_eq!(slugger.slug("Intro"), "intro");
+        assert_eq!(slugger.slug("Intro"), "intro-1");
+        assert_eq!(slugger.slug("Intro-2"), "intro-2");
+        assert_eq!(slugger.slug("Intro"), "intro-3");
+        assert_eq!(slugger.slug(""), "");
+        assert_eq!(slugger.slug("???"), "");
+
+        slugger.reset();
+        assert_eq!(slugger.slug("Intro"), "intro");
+    }
+
+    #[test]
+    fn jsx_component_and_mdx_escape() {
+        assert_eq!(
+            jsx_component("Callout", &[("type", "tip")], "text\n"),
+            "<Callout type=\"tip\">\n\ntext\n\n</Callout>\n\n"
+        );
+        assert_eq!(
+            jsx_component("Embed", &[("src", "https://a.b/?x=\"1\"")], ""),
+            "<Embed src=\"https://a.b/?x=&quot;1&quot;\" />\n\n"
+        );
+        assert_eq!(mdx_escape("a < b {c}"), "a \\< b \\{c\\}");
+    }
+
+    #[test]
+    fn escape_text_follows_output_format() {
+        let text = "if a < b { c }";
+        assert_eq!(escape_text(text, &Config::default()), text);
+
+        let html = Config {
+            escape_html: true,
+            ..Default::default()
+        };
+        assert_eq!(escape_text(text, &html), "if a &lt; b { c }");
+
+        let mdx = Config {
+            output_format: OutputFormat::Mdx,
+            ..Default::default()
+        };
+        assert_eq!(escape_text(text, &mdx), "if a \\< b \\{ c \\}");
+    }
+
     // --------------- Markdown Table ----------
     #[test]
     fn simple_table() {
@@ -276,18 +1289,108 @@ mod tests {
         assert_eq!(table(&mock), expected);
     }
 
+    #[test]
+    fn aligned_table_pads_and_aligns() {
+        let rows = vec![vec!["name", "n"], vec!["a", "10"], vec!["bcdef", "2"]];
+        let alignments = [TableAlignment::Center, TableAlignment::Right];
+        assert_eq!(
+            aligned_table(&rows, &alignments, true),
+            "| name  |   n |\n| :---: | --: |\n|   a   |  10 |\n| bcdef |   2 |"
+        );
+        assert_eq!(
+            aligned_table(&rows, &alignments, false),
+            "| name | n |\n| :-: | --: |\n| a | 10 |\n| bcdef | 2 |"
+        );
+        assert_eq!(
+            aligned_table(&rows[..1], &[], false),
+            "| name | n |\n| --- | --- |"
+        );
+    }
+
+    #[test]
+    fn csv_row_quotes_special_fields() {
+        assert_eq!(csv_row(&["a", "b c"]), "a,b c\n");
+        assert_eq!(
+            csv_row(&["x,y", "say \"hi\"", "line\nbreak"]),
+            "\"x,y\",\"say \"\"hi\"\"\",\"line\nbreak\"\n"
+        );
+    }
+
+    #[test]
+    fn transpose_pads_short_rows() {
+        let rows = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["1".to_string()],
+        ];
+        assert_eq!(transpose(&rows), vec![vec!["a", "1"], vec!["b", ""]]);
+    }
+
+    #[test]
+    fn table_cell_escapes_pipes_and_newlines() {
+        assert_eq!(table_cell("**a|b**"), "**a\\|b**");
+        assert_eq!(table_cell("a\nb"), "a<br>b");
+    }
+
+    #[test]
+    fn html_table_with_headers_and_lists() {
+        let rows = vec![
+            vec!["name".to_string(), "items".to_string()],
+            vec!["a".to_string(), "- x\n- y".to_string()],
+            vec!["b".to_string(), "line1\nline2".to_string()],
+        ];
+        let expected = "<table>\n\
+            <tr><th>name</th><th>items</th></tr>\n\
+            <tr><th>a</th><td><ul><li>x</li><li>y</li></ul></td></tr>\n\
+            <tr><th>b</th><td>line1<br>line2</td></tr>\n\
+            </table>";
+        assert_eq!(html_table(&rows, true, true), expected);
+    }
+
     // --------------- Text Annotations --------
     #[test]
     fn inline_code_test() {
         assert_eq!(inline_code("simple text"), "`simple text`");
     }
 
+    #[test]
+    fn inline_code_with_backticks() {
+        assert_eq!(inline_code("a`b"), "``a`b``");
+        assert_eq!(inline_code("`x`y`"), "`` `x`y` ``");
+        assert_eq!(inline_code("``double``"), "``` ``double`` ```");
+    }
+
     #[test]
     fn code_block_test() {
         let expected = "```javascript\nsimple text\n```";
         assert_eq!(code_block("simple text", Some("javascript")), expected);
     }
 
+    #[test]
+    fn code_block_fence_longer_than_content() {
+        let code = "```rust\nfn main() {}\n```";
+        let expected = "````markdown\n```rust\nfn main() {}\n```\n````";
+        assert_eq!(code_block(code, Some("markdown")), expected);
+    }
+
+    #[test]
+    fn code_block_tilde_fence() {
+        let code = "```\nnested\n```";
+        let expected = "~~~markdown\n```\nnested\n```\n~~~";
+        assert_eq!(code_block_with_fence(code, Some("markdown"), '~'), expected);
+    }
+
+    #[test]
+    fn code_block_omits_plaintext_label() {
+        assert_eq!(
+            code_block_with_options("text", None, '`', true),
+            "```\ntext\n```"
+        );
+        assert_eq!(
+            code_block_with_options("text", Some("rust"), '`', true),
+            "```rust\ntext\n```"
+        );
+    }
+
     #[test]
     fn inline_equation_test() {
         assert_eq!(inline_equation("E = mc^2"), "$E = mc^2$");
@@ -299,6 +1402,15 @@ mod tests {
         assert_eq!(equation("E = mc^2"), expected);
     }
 
+    #[test]
+    fn single_line_equation_test() {
+        assert_eq!(single_line_equation("E = mc^2"), "$$E = mc^2$$");
+        assert_eq!(
+            single_line_equation("a = b \\\\\n\n  c = d\n"),
+            "$$a = b \\\\ c = d$$"
+        );
+    }
+
     #[test]
     fn bold_test() {
         assert_eq!(bold("simple text"), "**simple text**");
@@ -314,11 +1426,238 @@ mod tests {
         assert_eq!(strikethrough("simple text"), "~~simple text~~");
     }
 
+    #[test]
+    fn caption_test() {
+        assert_eq!(caption("A photo"), "*A photo*");
+    }
+
     #[test]
     fn underline_test() {
         assert_eq!(underline("simple text"), "<u>simple text</u>");
     }
 
+    #[test]
+    fn apply_bold_italic_annotations() {
+        let a = Annotations {
+            bold: true,
+            italic: true,
+            ..Annotations::default()
+        };
+        assert_eq!(
+            apply_annotations("text", &a, &Config::default()),
+            "***text***"
+        );
+    }
+
+    #[test]
+    fn apply_strikethrough_code_annotations() {
+        let a = Annotations {
+            strikethrough: true,
+            code: true,
+            ..Annotations::default()
+        };
+        assert_eq!(
+            apply_annotations("text", &a, &Config::default()),
+            "`~~text~~`"
+        );
+    }
+
+    #[test]
+    fn empty_content_skips_annotations() {
+        let a = Annotations {
+            bold: true,
+            italic: true,
+            ..Annotations::default()
+        };
+        assert_eq!(apply_annotations("", &a, &Config::default()), "");
+        assert_eq!(apply_annotations(" ", &a, &Config::default()), " ");
+    }
+
+    #[test]
+    fn underline_and_color_require_config() {
+        let a = Annotations {
+            bold: true,
+            underline: true,
+            color: TextColor::RedBackground,
+            ..Annotations::default()
+        };
+        assert_eq!(
+            apply_annotations("text", &a, &Config::default()),
+            "**text**"
+        );
+
+        let config = Config {
+            render_underline: true,
+            render_color: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            apply_annotations("text", &a, &config),
+            "<span style=\"background-color: red\"><u>**text**</u></span>"
+        );
+    }
+
+    #[test]
+    fn annotation_nesting_order_is_fixed() {
+        let config = Config {
+            render_underline: true,
+            ..Config::default()
+        };
+        for bits in 0..16u8 {
+            let a = Annotations {
+                bold: bits & 1 != 0,
+                italic: bits & 2 != 0,
+                strikethrough: bits & 4 != 0,
+                underline: bits & 8 != 0,
+                ..Annotations::default()
+            };
+            let mut expected = "text".to_string();
+            if a.bold {
+                expected = bold(&expected);
+            }
+            if a.italic {
+                expected = format!("*{}*", expected);
+            }
+            if a.strikethrough {
+                expected = strikethrough(&expected);
+            }
+            if a.underline {
+                expected = underline(&expected);
+            }
+            assert_eq!(apply_annotations("text", &a, &config), expected, "{:?}", a);
+        }
+
+        let a = Annotations {
+            bold: true,
+            italic: true,
+            strikethrough: true,
+            underline: true,
+            ..Annotations::default()
+        };
+        assert_eq!(
+            apply_annotations("text", &a, &config),
+            "<u>~~***text***~~</u>"
+        );
+    }
+
+    #[test]
+    fn normalize_notion_id_forms() {
+        let dashed = "01234567-89ab-cdef-0123-456789abcdef";
+        assert_eq!(normalize_notion_id(dashed), dashed);
+        assert_eq!(
+            normalize_notion_id("0123456789abcdef0123456789abcdef"),
+            dashed
+        );
+        assert_eq!(
+            normalize_notion_id("0123456789ABCDEF0123456789ABCDEF"),
+            dashed
+        );
+        assert_eq!(
+            normalize_notion_id(" 01234567-89AB-cdef-0123-456789abcdef\n"),
+            dashed
+        );
+        assert_eq!(normalize_notion_id("page"), "page");
+        assert_eq!(normalize_notion_id(""), "");
+    }
+
+    #[test]
+    fn local_asset_path_styles() {
+        let web = AssetPathStyle::default();
+        assert_eq!(
+            local_asset_path("assets\\my photo.png", &web),
+            "assets/my%20photo.png"
+        );
+        let windows = AssetPathStyle {
+            separator: '\\',
+            encode_spaces: false,
+        };
+        assert_eq!(
+            local_asset_path("assets/my photo.png", &windows),
+            "assets\\my photo.png"
+        );
+    }
+
+    #[test]
+    fn extract_page_id_from_urls() {
+        let id = "0123456789abcdef0123456789abcdef";
+        for url in [
+            "https://www.notion.so/0123456789abcdef0123456789abcdef",
+            "https://www.notion.so/workspace/My-Page-0123456789abcdef0123456789abcdef",
+            "https://www.notion.so/workspace/My-Page-01234567-89ab-cdef-0123-456789abcdef",
+            "https://workspace.notion.site/My-Page-0123456789ABCDEF0123456789ABCDEF/",
+            "https://www.notion.so/0123456789abcdef0123456789abcdef?v=fedcba9876543210fedcba9876543210",
+            "https://www.notion.so/My-Page-0123456789abcdef0123456789abcdef?pvs=4#heading",
+            "https://www.notion.so/ffffffffffffffffffffffffffffffff?v=1&p=0123456789abcdef0123456789abcdef&pm=s",
+            "01234567-89ab-cdef-0123-456789abcdef",
+        ] {
+            assert_eq!(extract_page_id(url).as_deref(), Some(id), "{}", url);
+        }
+        assert_eq!(
+            extract_page_id("https://www.notion.so/workspace/My-Page"),
+            None
+        );
+        assert_eq!(extract_page_id("https://www.notion.so/abc"), None);
+    }
+
+    #[test]
+    fn color_foreground() {
+        assert_eq!(
+            color("text", &TextColor::Blue),
+            "<span style=\"color: blue\">text</span>"
+        );
+        assert_eq!(color("text", &TextColor::Default), "text");
+    }
+
+    #[test]
+    fn image_dimensions_from_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 2, 128, 0, 0, 1, 224]);
+        assert_eq!(image_dimensions(&png), Some((640, 480)));
+
+        let gif = b"GIF89a\x20\x00\x10\x00";
+        assert_eq!(image_dimensions(gif), Some((32, 16)));
+
+        // SOI, APP0 (length 4), SOF0 with height 200 and width 300
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, 0xff, 0xc0, 0x00, 0x11, 0x08, 0x00,
+            0xc8, 0x01, 0x2c,
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((300, 200)));
+
+        assert_eq!(image_dimensions(b"not an image"), None);
+        assert_eq!(image_dimensions(&png[..18]), None);
+    }
+
+    #[test]
+    fn line_endings() {
+        let text = "a\r\nb\nc\n";
+        assert_eq!(apply_line_ending(text, LineEnding::Lf), "a\nb\nc\n");
+        assert_eq!(apply_line_ending(text, LineEnding::CrLf), "a\r\nb\r\nc\r\n");
+    }
+
+    #[test]
+    fn badge_uses_background_color() {
+        assert_eq!(
+            badge("Done", &Color::Green),
+            "<span style=\"background-color: green\">Done</span>"
+        );
+        assert_eq!(badge("Done", &Color::Default), "Done");
+    }
+
+    #[test]
+    fn color_div_and_name() {
+        assert_eq!(
+            color_div("> note\n\n", &TextColor::YellowBackground),
+            "<div style=\"background-color: yellow\">\n\n> note\n\n</div>\n\n"
+        );
+        assert_eq!(color_div("> note\n\n", &TextColor::Default), "> note\n\n");
+        assert_eq!(
+            color_name(&TextColor::YellowBackground),
+            "yellow_background"
+        );
+        assert_eq!(color_name(&TextColor::Gray), "gray");
+    }
+
     // ---------------- Headings ---------------
     #[test]
     fn heading1_test() {
@@ -337,6 +1676,7 @@ mod tests {
     #[test]
     fn bullet_test() {
         assert_eq!(bullet("simple text", None), "- simple text");
+        assert_eq!(bullet("first\nsecond", Some(10)), "10. first\n    second");
     }
 
     #[test]
@@ -356,7 +1696,16 @@ mod tests {
         assert_eq!(out, "![simple text](https://example.com/image)");
     }
 
+    // `cargo test --no-default-features` builds without reqwest/base64 and runs the stub test below.
     #[test]
+    #[cfg(not(feature = "image-embed"))]
+    fn image_without_embed_feature_keeps_url() {
+        let out = image("simple text", "https://example.com/image", true).unwrap();
+        assert_eq!(out, "![simple text](https://example.com/image)");
+    }
+
+    #[test]
+    #[cfg(feature = "image-embed")]
     #[ignore] // Network call – run with `cargo test -- --ignored` to include.
     fn image_to_base64() {
         let md = image(
@@ -370,6 +1719,103 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn escape_outer_tag_keeps_content() {
+        assert_eq!(escape_outer_tag("<u>a < b</u>"), "&lt;u>a < b&lt;/u>");
+        assert_eq!(escape_outer_tag("plain"), "plain");
+    }
+
+    #[test]
+    fn format_bytes_test() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(1_258_291), "1.2 MB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GB");
+    }
+
+    #[test]
+    fn file_name_from_url_test() {
+        assert_eq!(
+            file_name_from_url("https://example.com/images/photo.jpg?v=2#top").as_deref(),
+            Some("photo.jpg")
+        );
+        assert_eq!(file_name_from_url("https://example.com/"), None);
+        assert_eq!(file_name_from_url("https://example.com"), None);
+    }
+
+    // ---------------- Link cards ------------
+    #[test]
+    fn open_graph_to_link_card() {
+        let html = r#"<html><head><title>Fallback</title>
+<meta property="og:title" content="Rust &amp; Notion">
+<meta property="og:description" content="A <converter>">
+<meta content="https://example.com/og.png" property="og:image">
+</head></html>"#;
+        let metadata = open_graph(html).unwrap();
+        assert_eq!(metadata.title, "Rust & Notion");
+        assert_eq!(
+            metadata.image.as_deref(),
+            Some("https://example.com/og.png")
+        );
+
+        assert_eq!(
+            open_graph("<title> Only title </title>").unwrap().title,
+            "Only title"
+        );
+        assert!(open_graph("<p>no metadata</p>").is_none());
+    }
+
+    // ---------------- Wrapping ---------------
+    #[test]
+    fn wrap_long_paragraph() {
+        let text = "the quick brown fox jumps over the lazy dog";
+        assert_eq!(
+            wrap_markdown(text, 20),
+            "the quick brown fox\njumps over the lazy\ndog"
+        );
+    }
+
+    #[test]
+    fn wrap_keeps_links_and_code_spans_whole() {
+        let text =
+            "see [the notion docs](https://developers.notion.com) and `cargo test --all` here";
+        assert_eq!(
+            wrap_markdown(text, 20),
+            "see\n[the notion docs](https://developers.notion.com)\nand\n`cargo test --all`\nhere"
+        );
+    }
+
+    #[test]
+    fn wrap_keeps_double_backtick_code_spans_whole() {
+        let text = format!("run {} now", inline_code("a `b` c"));
+        assert_eq!(text, "run ``a `b` c`` now");
+        assert_eq!(wrap_markdown(&text, 8), "run\n``a `b` c``\nnow");
+        // A back‑tick without a matching closing run is literal text.
+        assert_eq!(wrap_markdown("a ` b c", 3), "a `\nb c");
+    }
+
+    #[test]
+    fn wrap_keeps_list_indentation() {
+        let text = "- alpha beta gamma delta epsilon";
+        assert_eq!(
+            wrap_markdown(text, 16),
+            "- alpha beta\n  gamma delta\n  epsilon"
+        );
+    }
+
+    #[test]
+    fn wrap_skips_long_fences() {
+        let text = "````\n```\na very long line inside a code block\n```\n````";
+        assert_eq!(wrap_markdown(text, 10), text);
+    }
+
+    #[test]
+    fn wrap_skips_code_blocks_and_tables() {
+        let text = "```\na very long line inside a code block\n```\n| a very long table cell | b |";
+        assert_eq!(wrap_markdown(text, 10), text);
+    }
+
     // ---------------- Toggle -----------------
     #[test]
     fn toggle_no_summary() {
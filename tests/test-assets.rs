@@ -0,0 +1,192 @@
+#![cfg(feature = "asset-download")]
+
+use anyhow::Result;
+use notion2md::assets::AssetDownloader;
+use notion2md::builder::NotionToMarkdownBuilder;
+use notion2md::config::HtmlMode;
+use notion2md::notion_client::block::*;
+use notion2md::notion_client::file::{ExternalFile, File};
+use notion2md::notion_client::Client;
+use notion2md::notion_to_md::{assets, AssetKind, AssetRef, BlockWithChildren};
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// 幅 3・高さ 2 の PNG のヘッダー（IHDR まで）
+const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x03\0\0\0\x02\x08\x06\0\0\0";
+
+// パスをそのまま本文として返す HTTP サーバー。`/missing` で始まるパスは 404 を返し、
+// `/png` で始まるパスは PNG_HEADER を返す
+async fn serve(listener: TcpListener) {
+    while let Ok((mut socket, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+            let (status, body) = if path.starts_with("/missing") {
+                ("404 Not Found", Vec::new())
+            } else if path.starts_with("/png") {
+                ("200 OK", PNG_HEADER.to_vec())
+            } else {
+                ("200 OK", format!("content of {}", path).into_bytes())
+            };
+            let mut response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .into_bytes();
+            response.extend(body);
+            let _ = socket.write_all(&response).await;
+        });
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("notion2md-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn asset(block_id: &str, url: String) -> AssetRef {
+    AssetRef {
+        block_id: block_id.to_string(),
+        kind: AssetKind::Image,
+        url,
+    }
+}
+
+#[tokio::test]
+async fn downloads_assets_concurrently_and_reports_failures() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let base = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(serve(listener));
+
+    let dir = temp_dir("download");
+    let assets = vec![
+        asset("a", format!("{}/img/one.png", base)),
+        asset("b", format!("{}/img/two.png", base)),
+        asset("c", format!("{}/missing/three.png", base)),
+        asset("d", format!("{}/files/four.pdf", base)),
+    ];
+    let report = AssetDownloader::new(&dir)
+        .concurrency(3)
+        .download(&assets)
+        .await?;
+
+    let saved = report
+        .downloaded
+        .iter()
+        .map(|downloaded| std::fs::read_to_string(&downloaded.path))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(
+        saved,
+        vec![
+            "content of /img/one.png",
+            "content of /img/two.png",
+            "content of /files/four.pdf",
+        ]
+    );
+    assert_eq!(report.downloaded[0].path, dir.join("a-one.png"));
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0.block_id, "c");
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+fn image_block(id: &str, url: &str) -> BlockWithChildren {
+    BlockWithChildren {
+        block: Block {
+            id: Some(id.to_string()),
+            block_type: BlockType::Image {
+                image: ImageValue {
+                    file_type: File::External {
+                        external: ExternalFile {
+                            url: url.to_string(),
+                        },
+                    },
+                },
+            },
+            ..Default::default()
+        },
+        children: Vec::new(),
+        comments: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn deduplicate_shares_one_file_between_references() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let base = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(serve(listener));
+
+    let logo = format!("{}/img/logo.png", base);
+    let blocks = vec![image_block("first", &logo), image_block("second", &logo)];
+    let dir = temp_dir("dedup");
+    let report = AssetDownloader::new(&dir)
+        .deduplicate(true)
+        .download(&assets(&blocks))
+        .await?;
+
+    assert_eq!(report.downloaded.len(), 2);
+    assert_eq!(report.downloaded[0].path, report.downloaded[1].path);
+    assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+    // 保存したパスで参照を書き換えると、2 つの画像が同じファイルを指す
+    let path = report.path_of(&logo).expect("downloaded").to_path_buf();
+    let client = Client::new("dummy-token".to_string(), None)?;
+    let converter = NotionToMarkdownBuilder::new(client)
+        .rewrite_urls(move |_| path.display().to_string())
+        .build();
+    let markdown = converter.render_tree(&blocks)?;
+    let reference = format!("![]({})", report.downloaded[0].path.display());
+    assert_eq!(markdown.matches(&reference).count(), 2);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn downloaded_png_yields_its_dimensions() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let base = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(serve(listener));
+
+    let png = format!("{}/png/dot.png", base);
+    let text = format!("{}/img/not-an-image.png", base);
+    let blocks = vec![image_block("png", &png), image_block("text", &text)];
+    let dir = temp_dir("dimensions");
+    let report = AssetDownloader::new(&dir)
+        .download(&assets(&blocks))
+        .await?;
+
+    assert_eq!(report.downloaded[0].dimensions, Some((3, 2)));
+    assert_eq!(report.downloaded[1].dimensions, None);
+
+    let client = Client::new("dummy-token".to_string(), None)?;
+    let converter = NotionToMarkdownBuilder::new(client)
+        .image_dimensions(report.dimensions())
+        .build();
+    assert_eq!(
+        converter.render_tree(&blocks)?,
+        format!(
+            "<img src=\"{}\" alt=\"\" width=\"3\" height=\"2\">\n\n![]({})\n\n",
+            png, text
+        )
+    );
+
+    let client = Client::new("dummy-token".to_string(), None)?;
+    let converter = NotionToMarkdownBuilder::new(client)
+        .image_dimensions(report.dimensions())
+        .html_mode(HtmlMode::Stripped)
+        .build();
+    assert_eq!(
+        converter.render_tree(&blocks[..1])?,
+        format!("![]({} \"3x2\")\n\n", png)
+    );
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
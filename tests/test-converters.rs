@@ -0,0 +1,1838 @@
+use anyhow::Result;
+use notion2md::builder::NotionToMarkdownBuilder;
+use notion2md::client::NotionClientBuilder;
+use notion2md::config::{
+    AnnotationKind, AssetPathStyle, BareLinkStyle, CalloutStyle, Config, DateFormat, HeadingAnchor,
+    HtmlMode, ImageDefaultAlt, ListIndent, ListKind, OutputFormat, QuoteStyle, TableAlignment,
+    TemplateMentionStyle, ToggleStyle, WideTableStyle,
+};
+use notion2md::notion_client::block::*;
+use notion2md::notion_client::comment::Comment;
+use notion2md::notion_client::emoji::Emoji;
+use notion2md::notion_client::file::{ExternalFile, File, HostedFile};
+use notion2md::notion_client::property::DatePropertyValue;
+use notion2md::notion_client::rich_text::{
+    Annotations, Equation, Link, LinkMentionMention, Mention, PageMention, RichText,
+    TemplateMention, TemplateMentionDate, Text,
+};
+use notion2md::notion_client::Client;
+use notion2md::notion_to_md::{BlockWithChildren, NotionToMarkdown};
+use notion2md::utils;
+
+// ネットワークを使わずにブロックツリーを組み立てて変換結果を確認する
+
+fn builder() -> NotionToMarkdownBuilder {
+    let client = Client::new("dummy-token".to_string(), None).expect("client");
+    NotionToMarkdownBuilder::new(client)
+}
+
+fn text(content: &str) -> Vec<RichText> {
+    vec![RichText::Text {
+        text: Text {
+            content: content.to_string(),
+            link: None,
+        },
+        annotations: None,
+        plain_text: None,
+        href: None,
+    }]
+}
+
+fn block(block_type: BlockType) -> BlockWithChildren {
+    BlockWithChildren {
+        block: Block {
+            block_type,
+            ..Default::default()
+        },
+        children: Vec::new(),
+        comments: Vec::new(),
+    }
+}
+
+fn bullet(content: &str) -> BlockWithChildren {
+    block(BlockType::BulletedListItem {
+        bulleted_list_item: BulletedListItemValue {
+            rich_text: text(content),
+            color: TextColor::Default,
+            children: None,
+        },
+    })
+}
+
+fn numbered(content: &str) -> BlockWithChildren {
+    block(BlockType::NumberedListItem {
+        numbered_list_item: NumberedListItemValue {
+            rich_text: text(content),
+            color: TextColor::Default,
+            children: None,
+        },
+    })
+}
+
+fn paragraph(content: &str) -> BlockWithChildren {
+    block(BlockType::Paragraph {
+        paragraph: ParagraphValue {
+            rich_text: text(content),
+            ..Default::default()
+        },
+    })
+}
+
+fn divider() -> BlockWithChildren {
+    block(BlockType::Divider {
+        divider: DividerValue {},
+    })
+}
+
+#[test]
+fn divider_between_list_items_splits_list_without_blank_lines() -> Result<()> {
+    let converter = builder().build();
+    let blocks = vec![bullet("a"), divider(), bullet("b")];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "- a\n---\n- b\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn divider_between_numbered_items_continues_numbering() -> Result<()> {
+    let converter = builder().build();
+    let blocks = vec![numbered("a"), divider(), numbered("b")];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "1. a\n---\n2. b\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn divider_outside_list_keeps_blank_line() -> Result<()> {
+    let converter = builder().build();
+    let blocks = vec![divider(), paragraph("b")];
+    assert_eq!(converter.convert_blocks_to_markdown(&blocks)?, "---\n\nb\n");
+    Ok(())
+}
+
+#[test]
+fn divider_spacing_loose_always_adds_blank_line() -> Result<()> {
+    let converter = builder()
+        .divider_spacing(notion2md::config::DividerSpacing::Loose)
+        .build();
+    let blocks = vec![bullet("a"), divider(), bullet("b")];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "- a\n---\n\n- b\n"
+    );
+    Ok(())
+}
+
+fn comment(id: &str, content: &str) -> Comment {
+    serde_json::from_value(serde_json::json!({
+        "object": "comment",
+        "id": id,
+        "parent": { "type": "block_id", "block_id": "block" },
+        "discussion_id": "discussion",
+        "created_time": "2024-01-01T00:00:00.000Z",
+        "last_edited_time": "2024-01-01T00:00:00.000Z",
+        "created_by": {
+            "object": "user",
+            "id": "user",
+            "type": "person",
+            "person": { "email": "user@example.com" }
+        },
+        "rich_text": [{
+            "type": "text",
+            "text": { "content": content, "link": null },
+            "plain_text": content,
+            "href": null
+        }]
+    }))
+    .expect("comment")
+}
+
+#[test]
+fn block_comment_becomes_footnote() -> Result<()> {
+    let converter = builder().include_comments(true).build();
+    let mut commented = paragraph("本文");
+    commented
+        .comments
+        .push(comment("1234abcd-0000-0000-0000-000000000000", "要確認"));
+    let blocks = vec![commented];
+
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "本文[^1234abcd000000000000000000000000]\n"
+    );
+    assert_eq!(
+        converter.comments_to_footnotes(&blocks),
+        "\n[^1234abcd000000000000000000000000]: 要確認\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn equation_block() -> Result<()> {
+    let converter = builder().build();
+    let blocks = vec![block(BlockType::Equation {
+        equation: EquationValue {
+            expression: "E = mc^2".to_string(),
+        },
+    })];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "$$\nE = mc^2\n$$\n\n"
+    );
+    Ok(())
+}
+
+fn today_mention() -> RichText {
+    RichText::Mention {
+        mention: Mention::TemplateMention {
+            template_mention: TemplateMention::TemplateMentionDate {
+                template_mention_date: TemplateMentionDate::Today,
+            },
+        },
+        annotations: Annotations::default(),
+        plain_text: "@Today".to_string(),
+        href: None,
+    }
+}
+
+#[test]
+fn template_mention_today_uses_token() {
+    let converter = builder()
+        .template_mention(TemplateMentionStyle::Token {
+            today: "{{date}}".to_string(),
+            now: "{{time}}".to_string(),
+        })
+        .build();
+    assert_eq!(converter.convert_rich_text(&[today_mention()]), "{{date}}");
+}
+
+#[test]
+fn template_mention_today_resolves_to_date() {
+    let converter = builder().build();
+    let rendered = converter.convert_rich_text(&[today_mention()]);
+    // YYYY-MM-DD
+    assert_eq!(rendered.len(), 10);
+    assert_ne!(rendered, "@Today");
+}
+
+fn image(file: File) -> BlockWithChildren {
+    block(BlockType::Image {
+        image: ImageValue { file_type: file },
+    })
+}
+
+fn hosted_file(url: &str) -> File {
+    File::File {
+        file: HostedFile {
+            url: url.to_string(),
+            expiry_time: chrono::Utc::now(),
+        },
+    }
+}
+
+fn external_file(url: &str) -> File {
+    File::External {
+        external: ExternalFile {
+            url: url.to_string(),
+        },
+    }
+}
+
+#[test]
+fn strict_expiring_urls_rejects_hosted_files() {
+    let converter = builder().strict_expiring_urls(true).build();
+    let blocks = vec![image(hosted_file("https://s3.amazonaws.com/image.png"))];
+    assert!(converter.convert_blocks_to_markdown(&blocks).is_err());
+}
+
+#[test]
+fn strict_expiring_urls_allows_external_files() -> Result<()> {
+    let converter = builder().strict_expiring_urls(true).build();
+    let blocks = vec![image(external_file("https://example.com/image.png"))];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "![](https://example.com/image.png)\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn strict_expiring_urls_allows_rewritten_hosted_files() -> Result<()> {
+    let converter = builder()
+        .strict_expiring_urls(true)
+        .rewrite_urls(|url| url.replace("s3.amazonaws.com", "cdn.example.net"))
+        .build();
+    let blocks = vec![image(hosted_file("https://s3.amazonaws.com/image.png"))];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "![](https://cdn.example.net/image.png)\n\n"
+    );
+
+    // 書き換えで変わらなかった URL は期限付きのままなのでエラーになる
+    let converter = builder()
+        .strict_expiring_urls(true)
+        .rewrite_urls(|url| url.to_string())
+        .build();
+    assert!(converter.convert_blocks_to_markdown(&blocks).is_err());
+    Ok(())
+}
+
+#[test]
+fn hosted_files_only_warn_by_default() -> Result<()> {
+    let converter = builder().build();
+    let blocks = vec![image(hosted_file("https://s3.amazonaws.com/image.png"))];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "![](https://s3.amazonaws.com/image.png)\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn paragraph_children_are_indented() -> Result<()> {
+    let converter = builder().build();
+    let mut parent = paragraph("親");
+    parent.children = vec![bullet("子1"), bullet("子2")];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[parent])?,
+        "親\n  - 子1\n  - 子2\n"
+    );
+    Ok(())
+}
+
+fn bold(content: &str) -> RichText {
+    RichText::Text {
+        text: Text {
+            content: content.to_string(),
+            link: None,
+        },
+        annotations: Some(Annotations {
+            bold: true,
+            ..Default::default()
+        }),
+        plain_text: None,
+        href: None,
+    }
+}
+
+fn table_row(cells: Vec<Vec<RichText>>) -> BlockWithChildren {
+    block(BlockType::TableRow {
+        table_row: TableRowsValue { cells },
+    })
+}
+
+fn table(rows: Vec<BlockWithChildren>) -> BlockWithChildren {
+    let mut table = block(BlockType::Table {
+        table: TableValue {
+            table_width: 2,
+            has_column_header: true,
+            has_row_header: false,
+            children: None,
+        },
+    });
+    table.children = rows;
+    table
+}
+
+#[test]
+fn table_cell_pipe_is_escaped_inside_annotations() -> Result<()> {
+    let converter = builder().build();
+    let blocks = vec![table(vec![
+        table_row(vec![text("a"), text("b")]),
+        table_row(vec![vec![bold("x|y")], text("z")]),
+    ])];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "| a | b |\n| --- | --- |\n| **x\\|y** | z |\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn wide_tables_are_truncated_or_transposed() -> Result<()> {
+    let rows = || {
+        vec![
+            table_row(vec![text("a"), text("b"), text("c")]),
+            table_row(vec![text("1"), text("2"), text("3")]),
+        ]
+    };
+
+    let truncated = builder().max_table_columns(2).build();
+    assert_eq!(
+        truncated.convert_blocks_to_markdown(&[table(rows())])?,
+        "| a | b |\n| --- | --- |\n| 1 | 2 |\n\n*Showing 2 of 3 columns*\n\n"
+    );
+
+    let transposed = builder()
+        .max_table_columns(2)
+        .wide_tables(WideTableStyle::Transpose)
+        .build();
+    assert_eq!(
+        transposed.convert_blocks_to_markdown(&[table(rows())])?,
+        "| a | 1 |\n| --- | --- |\n| b | 2 |\n| c | 3 |\n\n"
+    );
+
+    // 列数が上限以内なら何もしない
+    let within = builder().max_table_columns(3).build();
+    assert_eq!(
+        within.convert_blocks_to_markdown(&[table(rows())])?,
+        "| a | b | c |\n| --- | --- | --- |\n| 1 | 2 | 3 |\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn table_markdown_vs_html() -> Result<()> {
+    let rows = || {
+        vec![
+            table_row(vec![text("a"), text("b")]),
+            table_row(vec![text("1"), text("2")]),
+        ]
+    };
+
+    let markdown = builder().build();
+    assert_eq!(
+        markdown.convert_blocks_to_markdown(&[table(rows())])?,
+        "| a | b |\n| --- | --- |\n| 1 | 2 |\n\n"
+    );
+
+    let html = builder().table_as_html(true).build();
+    assert_eq!(
+        html.convert_blocks_to_markdown(&[table(rows())])?,
+        "<table>\n<tr><th>a</th><th>b</th></tr>\n<tr><td>1</td><td>2</td></tr>\n</table>\n\n"
+    );
+    Ok(())
+}
+
+fn toggle(content: &str, children: Vec<BlockWithChildren>) -> BlockWithChildren {
+    let mut toggle = block(BlockType::Toggle {
+        toggle: ToggleValue {
+            rich_text: text(content),
+            color: TextColor::Default,
+            children: None,
+        },
+    });
+    toggle.children = children;
+    toggle
+}
+
+#[test]
+fn numbered_item_with_toggle_and_numbered_sub_list() -> Result<()> {
+    let converter = builder().build();
+    let mut first = numbered("a");
+    first.children = vec![toggle("t", vec![numbered("x"), numbered("y")])];
+    let blocks = vec![first, numbered("b")];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "1. a\n  - t\n    1. x\n    2. y\n2. b\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn plain_text_headings_and_lists() -> Result<()> {
+    let converter = builder().output_format(OutputFormat::PlainText).build();
+    let mut emphasized = paragraph("");
+    if let BlockType::Paragraph { paragraph } = &mut emphasized.block.block_type {
+        paragraph.rich_text = [vec![bold("Bold")], text(" body")].concat();
+    }
+    let plain = converter.convert_blocks_to_markdown(&[heading("Title"), emphasized])?;
+    assert!(plain.starts_with("Title\n"), "{}", plain);
+    assert!(plain.ends_with("Bold body\n"), "{}", plain);
+    assert!(!plain.contains(['#', '*']), "{}", plain);
+
+    let mut parent = bullet("one");
+    parent.children = vec![bullet("nested")];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[parent, bullet("two")])?,
+        "• one\n  • nested\n• two\n"
+    );
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[numbered("first"), numbered("second")])?,
+        "1. first\n2. second\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn custom_list_markers() -> Result<()> {
+    let converter = builder()
+        .list_marker(|kind, depth, index| match (kind, depth) {
+            (ListKind::Numbered, 1) => format!("{}.", (b'a' + index as u8 - 1) as char),
+            (ListKind::Numbered, _) => format!("{}.", "i".repeat(index)),
+            (ListKind::Bulleted, _) => "*".to_string(),
+        })
+        .build();
+    let mut first = numbered("first");
+    first.children = vec![numbered("x"), numbered("y")];
+    let blocks = vec![first, numbered("second")];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "a. first\n  i. x\n  ii. y\nb. second\n"
+    );
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[bullet("one"), bullet("two")])?,
+        "* one\n* two\n"
+    );
+    Ok(())
+}
+
+fn page_mention(id: &str, title: &str) -> RichText {
+    RichText::Mention {
+        mention: Mention::Page {
+            page: PageMention { id: id.to_string() },
+        },
+        annotations: Annotations::default(),
+        plain_text: title.to_string(),
+        href: None,
+    }
+}
+
+#[test]
+fn page_mention_becomes_relative_link() {
+    let converter = builder()
+        .page_link(|id| (id == "page-1").then(|| "getting-started".to_string()))
+        .build();
+    let rich_text = vec![
+        page_mention("page-1", "はじめに"),
+        page_mention("page-2", "その他"),
+    ];
+    assert_eq!(
+        converter.convert_rich_text(&rich_text),
+        "[はじめに](./getting-started.md)その他"
+    );
+}
+
+#[test]
+fn child_page_uses_page_link() -> Result<()> {
+    let converter = builder()
+        .page_link(|id| (id == "page-1").then(|| "getting-started".to_string()))
+        .build();
+    let mut child = block(BlockType::ChildPage {
+        child_page: ChildPageValue {
+            title: "はじめに".to_string(),
+        },
+    });
+    child.block.id = Some("page-1".to_string());
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[child])?,
+        "[はじめに](./getting-started.md)\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn tight_and_loose_lists() -> Result<()> {
+    let blocks = || {
+        let mut first = bullet("a");
+        first.children = vec![bullet("a1"), bullet("a2")];
+        vec![first, bullet("b"), paragraph("after")]
+    };
+
+    let tight = builder().build();
+    assert_eq!(
+        tight.convert_blocks_to_markdown(&blocks())?,
+        "- a\n  - a1\n  - a2\n- b\nafter\n"
+    );
+
+    let loose = builder().loose_lists(true).build();
+    assert_eq!(
+        loose.convert_blocks_to_markdown(&blocks())?,
+        "- a\n  - a1\n  - a2\n\n- b\nafter\n"
+    );
+    Ok(())
+}
+
+fn inline_equation(expression: &str) -> RichText {
+    RichText::Equation {
+        equation: Equation {
+            expression: expression.to_string(),
+        },
+        annotations: Annotations::default(),
+        plain_text: expression.to_string(),
+        href: None,
+    }
+}
+
+#[test]
+fn linked_inline_equation() {
+    let converter = builder().build();
+    let mut linked = inline_equation("x");
+    if let RichText::Equation { href, .. } = &mut linked {
+        *href = Some("https://example.com/x".to_string());
+    }
+    assert_eq!(
+        converter.convert_rich_text(&[linked]),
+        "[$x$](https://example.com/x)"
+    );
+    assert_eq!(converter.convert_rich_text(&[inline_equation("x")]), "$x$");
+}
+
+#[test]
+fn empty_equation_falls_back_to_plain_text() {
+    let converter = builder().build();
+    let mut equation = inline_equation("");
+    if let RichText::Equation { plain_text, .. } = &mut equation {
+        *plain_text = "E = mc^2".to_string();
+    }
+    assert_eq!(converter.convert_rich_text(&[equation]), "$E = mc^2$");
+    assert_eq!(converter.convert_rich_text(&[inline_equation("")]), "");
+}
+
+#[test]
+fn equation_inside_table_cell() -> Result<()> {
+    let converter = builder().build();
+    let blocks = vec![table(vec![
+        table_row(vec![text("式"), text("意味")]),
+        table_row(vec![vec![inline_equation("|x| + y")], text("絶対値")]),
+    ])];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "| 式 | 意味 |\n| --- | --- |\n| $\\|x\\| + y$ | 絶対値 |\n\n"
+    );
+    Ok(())
+}
+
+fn callout(content: &str, emoji: &str, children: Vec<BlockWithChildren>) -> BlockWithChildren {
+    let mut callout = block(BlockType::Callout {
+        callout: CalloutValue {
+            rich_text: text(content),
+            icon: Some(Icon::Emoji(Emoji {
+                emoji: emoji.to_string(),
+            })),
+            color: TextColor::Default,
+        },
+    });
+    callout.children = children;
+    callout
+}
+
+#[test]
+fn callout_as_pandoc_div() -> Result<()> {
+    let converter = builder().callout_style(CalloutStyle::PandocDiv).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[callout(
+            "Remember",
+            "💡",
+            vec![paragraph("detail")]
+        )])?,
+        "::: tip\nRemember\n\ndetail\n:::\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn colored_callout_is_wrapped_in_color_div() -> Result<()> {
+    let converter = builder()
+        .callout_style(CalloutStyle::PandocDiv)
+        .render_color(true)
+        .build();
+    let mut block = callout("Remember", "💡", vec![]);
+    if let BlockType::Callout { callout } = &mut block.block.block_type {
+        callout.color = TextColor::BlueBackground;
+    }
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[block])?,
+        "<div style=\"background-color: blue\">\n\n::: tip\nRemember\n:::\n\n</div>\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn eleven_numbered_items_nested_under_bullet() -> Result<()> {
+    let converter = builder().build();
+    let mut parent = bullet("parent");
+    parent.children = (1..=11)
+        .map(|i| {
+            if i == 10 {
+                numbered("ten\nsecond line")
+            } else {
+                numbered(&format!("item {}", i))
+            }
+        })
+        .collect();
+
+    let markdown = converter.convert_blocks_to_markdown(&[parent])?;
+    let lines: Vec<&str> = markdown.lines().collect();
+    assert_eq!(lines[0], "- parent");
+    assert_eq!(lines[1], "  1. item 1");
+    assert_eq!(lines[9], "  9. item 9");
+    // 続きの行は番号ではなく本文の位置に揃う
+    assert_eq!(lines[10], "  10. ten");
+    assert_eq!(lines[11], "      second line");
+    assert_eq!(lines[12], "  11. item 11");
+    assert_eq!(lines.len(), 13);
+    Ok(())
+}
+
+fn code(content: &str, language: Language) -> BlockWithChildren {
+    block(BlockType::Code {
+        code: CodeValue {
+            caption: Vec::new(),
+            rich_text: text(content),
+            language,
+        },
+    })
+}
+
+#[test]
+fn gfm_code_caption_becomes_bold_title() -> Result<()> {
+    let mut captioned = code("fn main() {}", Language::Rust);
+    if let BlockType::Code { code } = &mut captioned.block.block_type {
+        code.caption = text("main.rs");
+    }
+    let gfm = builder().output_format(OutputFormat::Gfm).build();
+    assert_eq!(
+        gfm.convert_blocks_to_markdown(&[captioned])?,
+        "**main.rs**\n\n```rust\nfn main() {}\n```\n"
+    );
+    assert_eq!(
+        gfm.convert_blocks_to_markdown(&[code("x", Language::Rust)])?,
+        "```rust\nx\n```\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn code_language_alias_overrides_default_name() -> Result<()> {
+    let blocks = [
+        code("int x;", Language::CPlusPlus),
+        code("let x;", Language::Rust),
+    ];
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&blocks)?,
+        "```cplusplus\nint x;\n```\n```rust\nlet x;\n```\n"
+    );
+    let converter = builder().code_language_alias("c++", "cpp").build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "```cpp\nint x;\n```\n```rust\nlet x;\n```\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn plaintext_code_block_omits_language() -> Result<()> {
+    let converter = builder().omit_plaintext_language(true).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[code("plain", Language::PlainText)])?,
+        "```\nplain\n```\n"
+    );
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[code("fn main() {}", Language::Rust)])?,
+        "```rust\nfn main() {}\n```\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn image_default_alt_uses_file_name() -> Result<()> {
+    let converter = builder()
+        .image_default_alt(ImageDefaultAlt::FileName)
+        .build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[image(external_file(
+            "https://example.com/img/photo.jpg"
+        ))])?,
+        "![photo.jpg](https://example.com/img/photo.jpg)\n\n"
+    );
+
+    let converter = builder().build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[image(external_file(
+            "https://example.com/img/photo.jpg"
+        ))])?,
+        "![](https://example.com/img/photo.jpg)\n\n"
+    );
+    Ok(())
+}
+
+fn styled_link() -> Vec<RichText> {
+    vec![
+        RichText::Text {
+            text: Text {
+                content: "Rust".to_string(),
+                link: Some(Link {
+                    url: "https://www.rust-lang.org".to_string(),
+                }),
+            },
+            annotations: Some(Annotations {
+                bold: true,
+                color: notion2md::notion_client::rich_text::TextColor::Red,
+                ..Default::default()
+            }),
+            plain_text: None,
+            href: None,
+        },
+        text(" rocks")[0].clone(),
+    ]
+}
+
+#[test]
+fn standalone_rich_text_conversion() {
+    let colored = Config {
+        render_color: true,
+        ..Default::default()
+    };
+    let expected = "<span style=\"color: red\">**[Rust](https://www.rust-lang.org)**</span> rocks";
+    assert_eq!(
+        notion2md::utils::rich_text_to_markdown(&styled_link(), &colored),
+        expected
+    );
+    assert_eq!(
+        builder()
+            .render_color(true)
+            .build()
+            .convert_rich_text(&styled_link()),
+        expected
+    );
+    // 既定の設定では色を出力しない
+    assert_eq!(
+        NotionToMarkdown::rich_text_to_markdown(&styled_link()),
+        "**[Rust](https://www.rust-lang.org)** rocks"
+    );
+}
+
+fn quote(content: &str, color: TextColor) -> BlockWithChildren {
+    block(BlockType::Quote {
+        quote: QuoteValue {
+            rich_text: text(content),
+            color,
+            children: None,
+        },
+    })
+}
+
+#[test]
+fn colored_quote_as_callout() -> Result<()> {
+    let converter = builder().colored_quote_as_callout(true).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[quote("styled", TextColor::Blue)])?,
+        "> [!note] styled\n\n"
+    );
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[quote("plain", TextColor::Default)])?,
+        "> plain\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn quote_styles() -> Result<()> {
+    let blocks = [quote("Stay hungry", TextColor::Default)];
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&blocks)?,
+        "> Stay hungry\n\n"
+    );
+    assert_eq!(
+        builder()
+            .quote_style(QuoteStyle::Callout)
+            .build()
+            .convert_blocks_to_markdown(&blocks)?,
+        "> [!quote] Stay hungry\n\n"
+    );
+    assert_eq!(
+        builder()
+            .quote_style(QuoteStyle::Callout)
+            .callout_style(CalloutStyle::PandocDiv)
+            .build()
+            .convert_blocks_to_markdown(&blocks)?,
+        "::: quote\nStay hungry\n:::\n\n"
+    );
+    Ok(())
+}
+
+fn heading(content: &str) -> BlockWithChildren {
+    block(BlockType::Heading2 {
+        heading_2: HeadingsValue {
+            rich_text: text(content),
+            ..Default::default()
+        },
+    })
+}
+
+#[test]
+fn table_of_contents_lists_headings() -> Result<()> {
+    let heading = |level: u8, content: &str| {
+        let value = HeadingsValue {
+            rich_text: text(content),
+            ..Default::default()
+        };
+        block(match level {
+            1 => BlockType::Heading1 { heading_1: value },
+            2 => BlockType::Heading2 { heading_2: value },
+            _ => BlockType::Heading3 { heading_3: value },
+        })
+    };
+    let blocks = vec![
+        block(BlockType::TableOfContents {
+            table_of_contents: TableOfContentsValue {
+                color: TextColor::Default,
+            },
+        }),
+        heading(1, "Intro"),
+        heading(2, "Setup"),
+        heading(3, "Install"),
+        heading(2, "Setup"),
+        heading(1, "Usage"),
+    ];
+    let converter = builder().heading_anchors(HeadingAnchor::Pandoc).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "- [Intro](#intro)\n\
+         \x20 - [Setup](#setup)\n\
+         \x20   - [Install](#install)\n\
+         \x20 - [Setup](#setup-1)\n\
+         - [Usage](#usage)\n\n\
+         # Intro {#intro}\n\
+         ## Setup {#setup}\n\
+         ### Install {#install}\n\
+         ## Setup {#setup-1}\n\
+         # Usage {#usage}\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn two_pass_collects_headings_before_rendering() -> Result<()> {
+    let toc = || {
+        block(BlockType::TableOfContents {
+            table_of_contents: TableOfContentsValue {
+                color: TextColor::Default,
+            },
+        })
+    };
+    let converter = builder().two_pass(true).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[toc(), heading("Later"), toc()])?,
+        "- [Later](#later)\n\n## Later\n- [Later](#later)\n\n"
+    );
+
+    // 目次がなくても two_pass なら収集パスを通す
+    let conversion = converter.conversion_context(&[heading("Only")]);
+    assert_eq!(conversion.document().headings.len(), 1);
+    assert_eq!(conversion.toc_entries()[0].slug, "only");
+
+    let single_pass = builder().build();
+    let conversion = single_pass.conversion_context(&[heading("Only")]);
+    assert!(conversion.document().headings.is_empty());
+    Ok(())
+}
+
+#[test]
+fn heading_anchors_are_unique() -> Result<()> {
+    let blocks = vec![
+        heading("What's new?"),
+        heading("What's new?"),
+        heading("!!!"),
+    ];
+
+    let pandoc = builder().heading_anchors(HeadingAnchor::Pandoc).build();
+    assert_eq!(
+        pandoc.convert_blocks_to_markdown(&blocks)?,
+        "## What's new? {#whats-new}\n## What's new? {#whats-new-1}\n## !!!\n"
+    );
+    // 変換ごとに数え直す
+    assert_eq!(
+        pandoc.convert_blocks_to_markdown(&blocks[..1])?,
+        "## What's new? {#whats-new}\n"
+    );
+
+    let html = builder().heading_anchors(HeadingAnchor::Html).build();
+    assert_eq!(
+        html.convert_blocks_to_markdown(&blocks[..1])?,
+        "<a name=\"whats-new\"></a>\n## What's new?\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn nested_conversions_do_not_share_state() -> Result<()> {
+    // 変換の途中で別の文書を変換しても、スラッグは文書ごとに数える
+    let converter = builder()
+        .heading_anchors(HeadingAnchor::Pandoc)
+        .paragraph(|payload| {
+            payload
+                .owner
+                .convert_blocks_to_markdown(&[heading("Intro")])
+        })
+        .build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[heading("Intro"), paragraph("embedded")])?,
+        "## Intro {#intro}\n## Intro {#intro}\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn mdx_callout_and_toggle() -> Result<()> {
+    let converter = builder().output_format(OutputFormat::Mdx).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[callout("Use {props} <here>", "💡", vec![])])?,
+        "<Callout type=\"tip\">\n\nUse \\{props\\} \\<here>\n\n</Callout>\n\n"
+    );
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[toggle("Details", vec![paragraph("hidden")])])?,
+        "<Toggle title=\"Details\">\n\nhidden\n\n</Toggle>\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn titleless_toggle_in_details_style() -> Result<()> {
+    let blocks = [
+        toggle("", vec![paragraph("hidden")]),
+        toggle("More", vec![paragraph("shown")]),
+    ];
+    let converter = builder().toggle_style(ToggleStyle::Details).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "<details>\n<summary></summary>\n\nhidden\n\n</details>\n\n\
+         <details>\n<summary>More</summary>\n\nshown\n\n</details>\n\n"
+    );
+
+    let converter = builder()
+        .toggle_style(ToggleStyle::Details)
+        .empty_toggle_summary("Details")
+        .build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks[..1])?,
+        "<details>\n<summary>Details</summary>\n\nhidden\n\n</details>\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn quote_containing_details_toggle() -> Result<()> {
+    let mut quoted = quote("Q", TextColor::Default);
+    quoted.children = vec![
+        toggle("More", vec![paragraph("hidden")]),
+        paragraph("after"),
+    ];
+    let converter = builder().toggle_style(ToggleStyle::Details).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[quoted])?,
+        "> Q\n\
+         > <details>\n\
+         > <summary>More</summary>\n\
+         > \n\
+         > hidden\n\
+         > \n\
+         > </details>\n\
+         > \n\
+         > after\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn sensitive_characters_are_escaped_per_output_format() {
+    let code = RichText::Text {
+        text: Text {
+            content: "<T>{}".to_string(),
+            link: None,
+        },
+        annotations: Some(Annotations {
+            code: true,
+            ..Default::default()
+        }),
+        plain_text: None,
+        href: None,
+    };
+    let rich_text = vec![text("Vec<T> in {braces} ")[0].clone(), code];
+
+    let mdx = Config {
+        output_format: OutputFormat::Mdx,
+        ..Default::default()
+    };
+    assert_eq!(
+        notion2md::utils::rich_text_to_markdown(&rich_text, &mdx),
+        "Vec\\<T> in \\{braces\\} `<T>{}`"
+    );
+
+    let html = builder().escape_html(true).build();
+    assert_eq!(
+        html.convert_rich_text(&rich_text),
+        "Vec&lt;T> in {braces} `<T>{}`"
+    );
+
+    assert_eq!(
+        NotionToMarkdown::rich_text_to_markdown(&rich_text),
+        "Vec<T> in {braces} `<T>{}`"
+    );
+}
+
+#[test]
+fn render_tree_converts_hand_built_tree_offline() -> Result<()> {
+    let mut parent = bullet("parent");
+    parent.children = vec![bullet("child")];
+    let tree = vec![
+        heading("Overview"),
+        paragraph("A long paragraph that should be wrapped by the renderer"),
+        parent,
+        divider(),
+        code("fn main() {}", Language::Rust),
+    ];
+
+    let converter = builder().build();
+    let markdown = converter.render_tree(&tree)?;
+    assert_eq!(markdown, converter.convert_blocks_to_markdown(&tree)?);
+    assert!(markdown.starts_with("## Overview\n"));
+    assert!(markdown.contains("- parent\n  - child\n"));
+    assert!(markdown.contains("---\n"));
+    assert!(markdown.ends_with("```rust\nfn main() {}\n```\n"));
+
+    // convert_page と同じ後処理（折り返し）も適用される
+    let wrapped = builder().wrap_width(30).build().render_tree(&tree)?;
+    assert!(wrapped.contains("A long paragraph that should\nbe wrapped by the renderer\n"));
+    assert!(wrapped.contains("```rust\nfn main() {}\n```\n"));
+    Ok(())
+}
+
+#[test]
+fn converter_sees_parent_block_type() -> Result<()> {
+    let converter = builder()
+        .paragraph(|payload| {
+            let text = NotionToMarkdown::rich_text_to_markdown(&payload.value.rich_text);
+            match payload.context.parent {
+                Some(BlockType::BulletedListItem { .. }) => {
+                    Ok(format!("({}, depth {})\n", text, payload.context.depth))
+                }
+                _ => Ok(format!("{}\n\n", text)),
+            }
+        })
+        .build();
+
+    let mut item = bullet("item");
+    item.children = vec![paragraph("inside")];
+    let toggle = toggle("toggle", vec![paragraph("in toggle")]);
+
+    let markdown = converter.convert_blocks_to_markdown(&[paragraph("top"), item, toggle])?;
+    assert!(markdown.starts_with("top\n\n"));
+    assert!(markdown.contains("- item\n  (inside, depth 1)\n"));
+    // リスト以外の親では最上位と同じ出力になる
+    assert!(markdown.contains("in toggle\n"));
+    assert!(!markdown.contains("(in toggle"));
+    Ok(())
+}
+
+fn equation(expression: &str) -> BlockWithChildren {
+    block(BlockType::Equation {
+        equation: EquationValue {
+            expression: expression.to_string(),
+        },
+    })
+}
+
+#[test]
+fn equation_inside_callout_and_quote() -> Result<()> {
+    let converter = builder().build();
+    let mut energy = callout("Energy ", "💡", vec![equation("E = mc^2")]);
+    if let BlockType::Callout { callout } = &mut energy.block.block_type {
+        callout.rich_text.push(inline_equation("m"));
+    }
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[energy])?,
+        "> [!note] Energy $m$\n> $$E = mc^2$$\n\n"
+    );
+
+    let mut quoted = quote("Sum", TextColor::Default);
+    quoted.children = vec![equation("a + b\n= c")];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[quoted])?,
+        "> Sum\n> $$a + b = c$$\n\n"
+    );
+
+    // 引用記号を付けない出力では複数行のまま
+    let converter = builder().callout_style(CalloutStyle::PandocDiv).build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&[callout(
+            "Energy",
+            "💡",
+            vec![equation("E = mc^2")]
+        )])?,
+        "::: tip\nEnergy\n\n$$\nE = mc^2\n$$\n:::\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn trailing_newlines_per_block_type() -> Result<()> {
+    let blocks = vec![paragraph("a"), code("x", Language::Rust), paragraph("b")];
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&blocks)?,
+        "a\n```rust\nx\n```\nb\n"
+    );
+
+    let converter = builder()
+        .trailing_newlines("code", 2)
+        .trailing_newlines("paragraph", 1)
+        .build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "a\n```rust\nx\n```\n\nb\n"
+    );
+    Ok(())
+}
+
+fn date_mention(start: &str, time_zone: Option<&str>) -> RichText {
+    RichText::Mention {
+        mention: Mention::Date {
+            date: DatePropertyValue {
+                start: start.parse().expect("datetime"),
+                end: None,
+                time_zone: time_zone.map(str::to_string),
+            },
+        },
+        annotations: Annotations::default(),
+        plain_text: start.to_string(),
+        href: None,
+    }
+}
+
+#[test]
+fn date_mentions_use_date_format() {
+    let converter = builder()
+        .date_format(DateFormat {
+            date: "%b %-d, %Y".to_string(),
+            datetime: "%b %-d, %Y %H:%M (%Z)".to_string(),
+        })
+        .build();
+
+    // 日付だけ
+    assert_eq!(
+        converter.convert_rich_text(&[date_mention("2024-03-01T00:00:00Z", None)]),
+        "Mar 1, 2024"
+    );
+    // 日時
+    assert_eq!(
+        converter.convert_rich_text(&[date_mention("2024-03-01T09:30:00Z", None)]),
+        "Mar 1, 2024 09:30 (UTC)"
+    );
+    // タイムゾーン付きの日時はそのタイムゾーンで出力する
+    assert_eq!(
+        converter.convert_rich_text(&[date_mention("2024-03-01T00:00:00Z", Some("Asia/Tokyo"))]),
+        "Mar 1, 2024 09:00 (JST)"
+    );
+
+    // 設定しなければ plain_text のまま
+    assert_eq!(
+        builder()
+            .build()
+            .convert_rich_text(&[date_mention("2024-03-01T09:30:00Z", None)]),
+        "2024-03-01T09:30:00Z"
+    );
+}
+
+fn synced(
+    id: &str,
+    synced_from: Option<&str>,
+    children: Vec<BlockWithChildren>,
+) -> BlockWithChildren {
+    let mut synced = block(BlockType::SyncedBlock {
+        synced_block: SyncedBlockValue {
+            synced_from: synced_from.map(|block_id| SyncedFrom::BlockId {
+                block_id: block_id.to_string(),
+            }),
+            children: None,
+        },
+    });
+    synced.block.id = Some(id.to_string());
+    synced.children = children;
+    synced
+}
+
+#[test]
+fn synced_block_is_rendered_once() -> Result<()> {
+    let blocks = vec![
+        synced("original", None, vec![paragraph("shared")]),
+        paragraph("between"),
+        synced("ref-1", Some("original"), vec![paragraph("shared")]),
+        synced("ref-2", Some("original"), vec![paragraph("shared")]),
+    ];
+
+    let converter = builder().build();
+    let markdown = converter.convert_blocks_to_markdown(&blocks)?;
+    assert_eq!(markdown.matches("shared").count(), 1);
+    assert!(markdown.contains("between"));
+    // 変換ごとに数え直す
+    assert_eq!(converter.convert_blocks_to_markdown(&blocks)?, markdown);
+
+    let repeated = builder().repeat_synced_blocks(true).build();
+    assert_eq!(
+        repeated
+            .convert_blocks_to_markdown(&blocks)?
+            .matches("shared")
+            .count(),
+        3
+    );
+    Ok(())
+}
+
+#[test]
+fn bare_links_as_inline_or_autolink() -> Result<()> {
+    let blocks = vec![
+        block(BlockType::Bookmark {
+            bookmark: BookmarkValue {
+                caption: Vec::new(),
+                url: "https://example.com".to_string(),
+            },
+        }),
+        block(BlockType::LinkPreview {
+            link_preview: LinkPreviewValue {
+                url: "https://github.com/4kk11/notion2md".to_string(),
+            },
+        }),
+    ];
+
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&blocks)?,
+        "[https://example.com](https://example.com)\n\n\
+         [https://github.com/4kk11/notion2md](https://github.com/4kk11/notion2md)\n\n"
+    );
+    assert_eq!(
+        builder()
+            .bare_link_style(BareLinkStyle::Autolink)
+            .build()
+            .convert_blocks_to_markdown(&blocks)?,
+        "<https://example.com>\n\n<https://github.com/4kk11/notion2md>\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn empty_bold_run_is_not_wrapped() {
+    let bold = |content: &str| RichText::Text {
+        text: Text {
+            content: content.to_string(),
+            link: None,
+        },
+        annotations: Some(Annotations {
+            bold: true,
+            ..Default::default()
+        }),
+        plain_text: None,
+        href: None,
+    };
+    let rich_text = vec![
+        text("before")[0].clone(),
+        bold(""),
+        text(" after")[0].clone(),
+    ];
+    assert_eq!(
+        NotionToMarkdown::rich_text_to_markdown(&rich_text),
+        "before after"
+    );
+    assert_eq!(
+        NotionToMarkdown::rich_text_to_markdown(&[bold("a"), bold(" "), bold("b")]),
+        "**a** **b**"
+    );
+}
+
+#[test]
+fn table_alignment_center_and_right() -> Result<()> {
+    let blocks = vec![table(vec![
+        table_row(vec![text("商品"), text("price")]),
+        table_row(vec![text("りんご"), text("120")]),
+        table_row(vec![text("melon"), text("1500")]),
+    ])];
+
+    let padded = builder()
+        .table_alignment(vec![TableAlignment::Center, TableAlignment::Right])
+        .pad_tables(true)
+        .build();
+    assert_eq!(
+        padded.convert_blocks_to_markdown(&blocks)?,
+        "|  商品   | price |\n\
+         | :---: | ----: |\n\
+         |  りんご  |   120 |\n\
+         | melon |  1500 |\n\n"
+    );
+
+    let unpadded = builder()
+        .table_alignment(vec![TableAlignment::Center, TableAlignment::Right])
+        .build();
+    assert_eq!(
+        unpadded.convert_blocks_to_markdown(&blocks)?,
+        "| 商品 | price |\n| :-: | --: |\n| りんご | 120 |\n| melon | 1500 |\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn colored_callout_with_render_color() -> Result<()> {
+    let note = || {
+        let mut note = callout("Heads up", "💡", Vec::new());
+        if let BlockType::Callout { callout } = &mut note.block.block_type {
+            callout.color = TextColor::YellowBackground;
+        }
+        note
+    };
+
+    // 色の出力が無効なら色は付けない
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&[note()])?,
+        "> [!note] Heads up\n\n"
+    );
+    assert_eq!(
+        builder()
+            .render_color(true)
+            .build()
+            .convert_blocks_to_markdown(&[note()])?,
+        "<div style=\"background-color: yellow\">\n\n> [!note] Heads up\n\n</div>\n\n"
+    );
+    assert_eq!(
+        builder()
+            .render_color(true)
+            .output_format(OutputFormat::Mdx)
+            .build()
+            .convert_blocks_to_markdown(&[note()])?,
+        "<Callout type=\"tip\" color=\"yellow_background\">\n\nHeads up\n\n</Callout>\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn url_rewriter_prefixes_cdn_host() -> Result<()> {
+    let converter = builder()
+        .rewrite_urls(|url| {
+            format!(
+                "https://cdn.example.net/{}",
+                url.trim_start_matches("https://")
+            )
+        })
+        .build();
+    let blocks = vec![
+        image(external_file("https://example.com/img/photo.jpg")),
+        block(BlockType::Video {
+            video: VideoValue {
+                file_type: hosted_file("https://s3.amazonaws.com/clip.mp4"),
+            },
+        }),
+    ];
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        "![](https://cdn.example.net/example.com/img/photo.jpg)\n\n\
+         ![](https://cdn.example.net/s3.amazonaws.com/clip.mp4)\n\n"
+    );
+    Ok(())
+}
+
+fn to_do(content: &str, checked: bool) -> BlockWithChildren {
+    block(BlockType::ToDo {
+        to_do: ToDoValue {
+            rich_text: text(content),
+            checked: Some(checked),
+            color: Some(TextColor::Default),
+            children: None,
+        },
+    })
+}
+
+#[test]
+fn to_do_under_numbered_item_follows_list_indent() -> Result<()> {
+    let blocks = || {
+        let mut item = numbered("step");
+        let mut task = to_do("check", false);
+        task.children = vec![paragraph("note")];
+        item.children = vec![task, to_do("done", true)];
+        vec![item]
+    };
+
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&blocks())?,
+        "1. step\n  - [ ] check\n    note\n  - [x] done\n"
+    );
+    // 番号の幅にそろえると CommonMark でも番号付きリストの子になる
+    assert_eq!(
+        builder()
+            .list_indent(ListIndent::MarkerWidth)
+            .build()
+            .convert_blocks_to_markdown(&blocks())?,
+        "1. step\n   - [ ] check\n     note\n   - [x] done\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn image_references_are_defined_once() -> Result<()> {
+    let converter = builder().image_references(true).build();
+    let blocks = vec![
+        image(external_file("https://example.com/logo.png")),
+        paragraph("text"),
+        image(external_file("https://example.com/photo.jpg")),
+        image(external_file("https://example.com/logo.png")),
+    ];
+    let markdown = converter.convert_blocks_to_markdown(&blocks)?;
+    assert_eq!(
+        markdown,
+        "![][image-1]\n\ntext\n![][image-2]\n\n![][image-1]\n\n\
+         [image-1]: https://example.com/logo.png\n\
+         [image-2]: https://example.com/photo.jpg\n"
+    );
+    // 変換ごとに番号を振り直す
+    assert_eq!(converter.convert_blocks_to_markdown(&blocks)?, markdown);
+    Ok(())
+}
+
+#[test]
+fn divider_among_table_rows_is_skipped() -> Result<()> {
+    let rows = || {
+        vec![
+            table_row(vec![text("a"), text("b")]),
+            divider(),
+            table_row(vec![text("1"), text("2")]),
+        ]
+    };
+
+    let markdown = builder().build();
+    assert_eq!(
+        markdown.convert_blocks_to_markdown(&[table(rows())])?,
+        "| a | b |\n| --- | --- |\n| 1 | 2 |\n\n"
+    );
+
+    let html = builder().table_as_html(true).build();
+    assert_eq!(
+        html.convert_blocks_to_markdown(&[table(rows())])?,
+        "<table>\n<tr><th>a</th><th>b</th></tr>\n<tr><td>1</td><td>2</td></tr>\n</table>\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn group_list_items_merges_consecutive_items_of_one_kind() {
+    let blocks = vec![
+        paragraph("intro"),
+        bullet("a"),
+        bullet("b"),
+        numbered("one"),
+        to_do("task", false),
+        to_do("done", true),
+        paragraph("outro"),
+        bullet("c"),
+    ];
+    let sizes = utils::group_list_items(&blocks)
+        .iter()
+        .map(|group| group.len())
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, vec![1, 2, 1, 2, 1, 1]);
+    assert!(utils::group_list_items(&[]).is_empty());
+}
+
+fn annotated(content: &str, annotations: Annotations) -> RichText {
+    RichText::Text {
+        text: Text {
+            content: content.to_string(),
+            link: None,
+        },
+        annotations: Some(annotations),
+        plain_text: Some(content.to_string()),
+        href: None,
+    }
+}
+
+#[test]
+fn annotation_order_changes_nesting() {
+    let rich_text = vec![annotated(
+        "text",
+        Annotations {
+            bold: true,
+            strikethrough: true,
+            underline: true,
+            ..Annotations::default()
+        },
+    )];
+
+    let converter = builder().render_underline(true).build();
+    assert_eq!(
+        converter.convert_rich_text(&rich_text),
+        "<u>~~**text**~~</u>"
+    );
+
+    let converter = builder()
+        .render_underline(true)
+        .annotation_order(vec![
+            AnnotationKind::Underline,
+            AnnotationKind::Strikethrough,
+        ])
+        .build();
+    assert_eq!(
+        converter.convert_rich_text(&rich_text),
+        "**~~<u>text</u>~~**"
+    );
+}
+
+#[test]
+fn builder_from_token_keeps_config() -> Result<()> {
+    let config = Config {
+        output_format: OutputFormat::Mdx,
+        ..Config::default()
+    };
+    let converter = NotionToMarkdownBuilder::from_token("dummy-token", config)?
+        .escape_html(true)
+        .build();
+    assert_eq!(converter.config.output_format, OutputFormat::Mdx);
+    assert!(converter.config.escape_html);
+    Ok(())
+}
+
+#[test]
+fn builder_from_client_builder_keeps_config() -> Result<()> {
+    let config = Config {
+        output_format: OutputFormat::Mdx,
+        ..Config::default()
+    };
+    let client = NotionClientBuilder::new("dummy-token")
+        .timeout(std::time::Duration::from_secs(5))
+        .user_agent("notion2md-test");
+    let converter = NotionToMarkdownBuilder::from_client_builder(client, config)?.build();
+    assert_eq!(converter.config.output_format, OutputFormat::Mdx);
+    Ok(())
+}
+
+#[test]
+fn synced_original_matches_dashed_and_undashed_ids() -> Result<()> {
+    let blocks = vec![
+        synced(
+            "01234567-89ab-cdef-0123-456789abcdef",
+            None,
+            vec![paragraph("shared")],
+        ),
+        synced(
+            "ref",
+            Some("0123456789abcdef0123456789abcdef"),
+            vec![paragraph("shared")],
+        ),
+    ];
+    let markdown = builder().build().convert_blocks_to_markdown(&blocks)?;
+    assert_eq!(markdown.matches("shared").count(), 1);
+    Ok(())
+}
+
+fn link_mention(title: &str, url: &str, description: Option<&str>) -> RichText {
+    RichText::Mention {
+        mention: Mention::LinkMention {
+            link_mention: LinkMentionMention {
+                description: description.map(str::to_string),
+                href: Some(url.to_string()),
+                icon_url: None,
+                iframe_url: None,
+                link_author: None,
+                padding: None,
+                thumbnail_url: None,
+                title: title.to_string(),
+            },
+        },
+        annotations: Annotations::default(),
+        plain_text: url.to_string(),
+        href: Some(url.to_string()),
+    }
+}
+
+#[test]
+fn link_mention_uses_title() {
+    let rich_text = vec![
+        link_mention(
+            "Rust Blog",
+            "https://blog.rust-lang.org",
+            Some("Empowering everyone"),
+        ),
+        text(" / ").remove(0),
+        link_mention("", "https://example.com", None),
+    ];
+    assert_eq!(
+        builder().build().convert_rich_text(&rich_text),
+        "[Rust Blog](https://blog.rust-lang.org) / [https://example.com](https://example.com)"
+    );
+    assert_eq!(
+        builder()
+            .link_mention_descriptions(true)
+            .build()
+            .convert_rich_text(&rich_text),
+        "[Rust Blog](https://blog.rust-lang.org) — Empowering everyone / \
+         [https://example.com](https://example.com)"
+    );
+}
+
+#[test]
+fn faithful_spacing_keeps_blank_lines_in_lists() -> Result<()> {
+    let blocks = || {
+        let mut item = bullet("item");
+        item.children = vec![
+            image(external_file("https://example.com/a.png")),
+            paragraph("after"),
+        ];
+        vec![item, bullet("next")]
+    };
+
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&blocks())?,
+        "- item\n  ![](https://example.com/a.png)\n  after\n- next\n"
+    );
+    assert_eq!(
+        builder()
+            .faithful_spacing(true)
+            .build()
+            .convert_blocks_to_markdown(&blocks())?,
+        "- item\n  ![](https://example.com/a.png)\n\n  after\n- next\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn caption_subtext_adds_italic_line() -> Result<()> {
+    let blocks = vec![block(BlockType::Bookmark {
+        bookmark: BookmarkValue {
+            caption: text("Project home"),
+            url: "https://example.com".to_string(),
+        },
+    })];
+
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&blocks)?,
+        "[https://example.com](https://example.com)\n\n"
+    );
+    assert_eq!(
+        builder()
+            .caption_subtext(true)
+            .build()
+            .convert_blocks_to_markdown(&blocks)?,
+        "[https://example.com](https://example.com)\n\n*Project home*\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn template_renders_label_and_optional_body() -> Result<()> {
+    let blocks = || {
+        let mut template = block(BlockType::Template {
+            template: TemplateValue {
+                rich_text: text("Add a task"),
+                children: None,
+            },
+        });
+        template.children = vec![to_do("New task", false)];
+        vec![template]
+    };
+
+    assert_eq!(
+        builder().build().convert_blocks_to_markdown(&blocks())?,
+        "Add a task\n"
+    );
+    assert_eq!(
+        builder()
+            .inline_templates(true)
+            .build()
+            .convert_blocks_to_markdown(&blocks())?,
+        "Add a task\n\n- [ ] New task\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn inline_code_containing_backtick() {
+    let rich_text = vec![annotated(
+        "`x`y`",
+        Annotations {
+            code: true,
+            ..Annotations::default()
+        },
+    )];
+    assert_eq!(
+        builder().build().convert_rich_text(&rich_text),
+        "`` `x`y` ``"
+    );
+}
+
+#[test]
+fn localized_asset_paths_use_separator_and_encoded_spaces() -> Result<()> {
+    let localize = |url: &str| {
+        let name = url.rsplit('/').next().unwrap_or_default();
+        format!("assets\\{}", name.replace("%20", " "))
+    };
+    let blocks = || {
+        vec![
+            image(external_file("https://example.com/img/my%20photo.jpg")),
+            image(external_file("https://example.com/img/logo.png")),
+        ]
+    };
+
+    let converter = builder()
+        .rewrite_urls(localize)
+        .asset_path_style(AssetPathStyle::default())
+        .build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks())?,
+        "![](assets/my%20photo.jpg)\n\n![](assets/logo.png)\n\n"
+    );
+
+    // 書き換え後も URL のままなら手を加えない
+    let converter = builder()
+        .rewrite_urls(|url| url.replace("example.com", "cdn.example.net"))
+        .asset_path_style(AssetPathStyle::default())
+        .build();
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks()[1..])?,
+        "![](https://cdn.example.net/img/logo.png)\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn html_modes() -> Result<()> {
+    let rich_text = vec![annotated(
+        "text",
+        Annotations {
+            underline: true,
+            color: notion2md::notion_client::rich_text::TextColor::Red,
+            ..Default::default()
+        },
+    )];
+    let embed = || {
+        vec![block(BlockType::Embed {
+            embed: EmbedValue {
+                url: "https://example.com/widget".to_string(),
+            },
+        })]
+    };
+
+    let kept = builder().render_underline(true).render_color(true).build();
+    assert_eq!(
+        kept.convert_rich_text(&rich_text),
+        "<span style=\"color: red\"><u>text</u></span>"
+    );
+    assert_eq!(
+        kept.convert_blocks_to_markdown(&embed())?,
+        "<iframe src=\"https://example.com/widget\" width=\"100%\" height=\"500px\"></iframe>\n\n"
+    );
+
+    let escaped = builder()
+        .render_underline(true)
+        .render_color(true)
+        .html_mode(HtmlMode::Escaped)
+        .build();
+    assert_eq!(
+        escaped.convert_rich_text(&rich_text),
+        "&lt;span style=\"color: red\">&lt;u>text&lt;/u>&lt;/span>"
+    );
+    assert_eq!(
+        escaped.convert_blocks_to_markdown(&embed())?,
+        "```html\n<iframe src=\"https://example.com/widget\" width=\"100%\" height=\"500px\"></iframe>\n```\n\n"
+    );
+
+    let stripped = builder()
+        .render_underline(true)
+        .render_color(true)
+        .html_mode(HtmlMode::Stripped)
+        .build();
+    assert_eq!(stripped.convert_rich_text(&rich_text), "text");
+    assert_eq!(
+        stripped.convert_blocks_to_markdown(&embed())?,
+        "[https://example.com/widget](https://example.com/widget)\n\n"
+    );
+    Ok(())
+}
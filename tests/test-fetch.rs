@@ -0,0 +1,1181 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use notion2md::builder::NotionToMarkdownBuilder;
+use notion2md::config::{LineEnding, PageCover, PropertyOutput};
+use notion2md::notion_client::block::*;
+use notion2md::notion_client::blocks::retrieve::response::RetrieveBlockChilerenResponse;
+use notion2md::notion_client::comment::Comment;
+use notion2md::notion_client::comments::retrieve::response::RetrieveCommentsResponse;
+use notion2md::notion_client::databases::query::response::QueryDatabaseResponse;
+use notion2md::notion_client::file::{ExternalFile, File, HostedFile};
+use notion2md::notion_client::page::{
+    Color, Page, PageProperty, RelationPropertyValue, SelectPropertyValue,
+};
+use notion2md::notion_client::parent::Parent;
+use notion2md::notion_client::rich_text::{RichText, Text};
+use notion2md::notion_client::{Client, NotionClientError};
+use notion2md::notion_to_md::{AssetKind, AssetRef, BlockWithChildren};
+use notion2md::source::{BlockSource, DatabaseView, LinkMetadata};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// モックを取得元にしたビルダー
+fn builder(source: impl BlockSource + 'static) -> NotionToMarkdownBuilder {
+    let client = Client::new("dummy-token".to_string(), None).expect("client");
+    NotionToMarkdownBuilder::new(client).block_source(source)
+}
+
+// 登録したデータをそのまま返すモック。
+// 子ブロックが登録されていないブロックは、権限のないブロックと同じく 404 を返す
+#[derive(Default)]
+struct MockSource {
+    children: HashMap<String, (Duration, Vec<Block>)>,
+    blocks: HashMap<String, Block>,
+    pages: HashMap<String, Page>,
+    parents: HashMap<String, Parent>,
+    titles: HashMap<String, String>,
+    rows: Vec<Page>,
+    link_metadata: HashMap<String, LinkMetadata>,
+    metadata_calls: AtomicUsize,
+    file_sizes: HashMap<String, u64>,
+    database_view: Option<DatabaseView>,
+    comments: HashMap<String, Vec<Comment>>,
+    // コメント取得がこの回数だけ 429 を返す
+    comment_rate_limits: AtomicUsize,
+}
+
+impl MockSource {
+    fn children(self, block_id: &str, blocks: Vec<Block>) -> Self {
+        self.delayed_children(block_id, Duration::ZERO, blocks)
+    }
+
+    // 応答を delay だけ遅らせる
+    fn delayed_children(mut self, block_id: &str, delay: Duration, blocks: Vec<Block>) -> Self {
+        self.children.insert(block_id.to_string(), (delay, blocks));
+        self
+    }
+
+    fn block(mut self, block: Block) -> Self {
+        self.blocks
+            .insert(block.id.clone().unwrap_or_default(), block);
+        self
+    }
+
+    fn page(mut self, page: Page) -> Self {
+        self.pages.insert(page.id.clone(), page);
+        self
+    }
+
+    fn parent(mut self, page_id: &str, parent: Parent) -> Self {
+        self.parents.insert(page_id.to_string(), parent);
+        self
+    }
+
+    // ページ・データベースのタイトル
+    fn title(mut self, id: &str, title: &str) -> Self {
+        self.titles.insert(id.to_string(), title.to_string());
+        self
+    }
+
+    fn rows(mut self, rows: Vec<Page>) -> Self {
+        self.rows = rows;
+        self
+    }
+
+    // 登録のない URL の Open Graph 情報の取得は失敗する
+    fn link_metadata(mut self, url: &str, metadata: LinkMetadata) -> Self {
+        self.link_metadata.insert(url.to_string(), metadata);
+        self
+    }
+
+    fn file_size(mut self, url: &str, size: u64) -> Self {
+        self.file_sizes.insert(url.to_string(), size);
+        self
+    }
+
+    fn database_view(mut self, view: DatabaseView) -> Self {
+        self.database_view = Some(view);
+        self
+    }
+
+    fn comment(mut self, block_id: &str, comment: Comment) -> Self {
+        self.comments
+            .entry(block_id.to_string())
+            .or_default()
+            .push(comment);
+        self
+    }
+
+    fn rate_limited_comments(self, times: usize) -> Self {
+        self.comment_rate_limits.store(times, Ordering::SeqCst);
+        self
+    }
+
+    // page → 1 → 2 → ... と 1 階層に 1 ブロックずつ、depth まで入れ子になったツリー
+    fn chain(depth: usize) -> Self {
+        (1..=depth).fold(Self::default(), |source, level| {
+            let parent = match level {
+                1 => "page".to_string(),
+                _ => (level - 1).to_string(),
+            };
+            let block = paragraph(
+                &level.to_string(),
+                &format!("level {}", level),
+                level < depth,
+            );
+            source.children(&parent, vec![block])
+        })
+    }
+}
+
+impl BlockSource for MockSource {
+    fn retrieve_block_children<'a>(
+        &'a self,
+        block_id: &'a str,
+        _start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<RetrieveBlockChilerenResponse>> {
+        Box::pin(async move {
+            let Some((delay, results)) = self.children.get(block_id).cloned() else {
+                return Err(not_found());
+            };
+            tokio::time::sleep(delay).await;
+            Ok(RetrieveBlockChilerenResponse {
+                object: "list".to_string(),
+                results,
+                next_cursor: None,
+                has_more: false,
+            })
+        })
+    }
+
+    fn retrieve_block<'a>(&'a self, block_id: &'a str) -> BoxFuture<'a, Result<Block>> {
+        Box::pin(async move {
+            match self.blocks.get(block_id) {
+                Some(block) => Ok(block.clone()),
+                None => anyhow::bail!("unexpected block {}", block_id),
+            }
+        })
+    }
+
+    fn retrieve_page<'a>(&'a self, page_id: &'a str) -> BoxFuture<'a, Result<Page>> {
+        Box::pin(async move {
+            match self.pages.get(page_id) {
+                Some(page) => Ok(page.clone()),
+                None => anyhow::bail!("unexpected page {}", page_id),
+            }
+        })
+    }
+
+    fn retrieve_page_parent<'a>(
+        &'a self,
+        page_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Parent>>> {
+        Box::pin(async move { Ok(self.parents.get(page_id).cloned()) })
+    }
+
+    fn query_database<'a>(
+        &'a self,
+        _database_id: &'a str,
+        _start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<QueryDatabaseResponse>> {
+        Box::pin(async move {
+            Ok(QueryDatabaseResponse {
+                object: "list".to_string(),
+                results: self.rows.clone(),
+                next_cursor: None,
+                has_more: false,
+            })
+        })
+    }
+
+    fn retrieve_title<'a>(&'a self, target: &'a Parent) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            Ok(match target {
+                Parent::PageId { page_id: id } | Parent::DatabaseId { database_id: id } => {
+                    self.titles.get(id).cloned()
+                }
+                _ => None,
+            })
+        })
+    }
+
+    fn retrieve_link_metadata<'a>(
+        &'a self,
+        url: &'a str,
+    ) -> BoxFuture<'a, Result<Option<LinkMetadata>>> {
+        Box::pin(async move {
+            self.metadata_calls.fetch_add(1, Ordering::SeqCst);
+            match self.link_metadata.get(url) {
+                Some(metadata) => Ok(Some(metadata.clone())),
+                None => anyhow::bail!("connection refused"),
+            }
+        })
+    }
+
+    fn retrieve_file_size<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Option<u64>>> {
+        Box::pin(async move { Ok(self.file_sizes.get(url).copied()) })
+    }
+
+    fn retrieve_database_view<'a>(
+        &'a self,
+        _block_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<DatabaseView>>> {
+        Box::pin(async move { Ok(self.database_view.clone()) })
+    }
+
+    fn retrieve_comments<'a>(
+        &'a self,
+        block_id: &'a str,
+        _start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<RetrieveCommentsResponse>> {
+        Box::pin(async move {
+            let limited =
+                self.comment_rate_limits
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+            if limited.is_ok() {
+                return Err(rate_limited());
+            }
+            Ok(RetrieveCommentsResponse {
+                object: "list".to_string(),
+                results: self.comments.get(block_id).cloned().unwrap_or_default(),
+                next_cursor: None,
+                has_more: false,
+            })
+        })
+    }
+}
+
+// Arc で包んだモックを builder に渡し、テスト側からも呼び出し回数などを参照できるようにする
+struct Shared<S>(Arc<S>);
+
+impl<S: BlockSource> BlockSource for Shared<S> {
+    fn retrieve_block_children<'a>(
+        &'a self,
+        block_id: &'a str,
+        start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<RetrieveBlockChilerenResponse>> {
+        self.0.retrieve_block_children(block_id, start_cursor)
+    }
+
+    fn query_database<'a>(
+        &'a self,
+        database_id: &'a str,
+        start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<QueryDatabaseResponse>> {
+        self.0.query_database(database_id, start_cursor)
+    }
+
+    fn retrieve_link_metadata<'a>(
+        &'a self,
+        url: &'a str,
+    ) -> BoxFuture<'a, Result<Option<LinkMetadata>>> {
+        self.0.retrieve_link_metadata(url)
+    }
+}
+
+fn api_error(status: u32, code: &str, message: &str) -> anyhow::Error {
+    NotionClientError::InvalidStatusCode {
+        error: notion2md::notion_client::error::Error {
+            object: "error".to_string(),
+            status,
+            code: code.to_string(),
+            message: message.to_string(),
+            request_id: None,
+        },
+    }
+    .into()
+}
+
+fn not_found() -> anyhow::Error {
+    api_error(404, "object_not_found", "Could not find block")
+}
+
+fn rate_limited() -> anyhow::Error {
+    api_error(429, "rate_limited", "Rate limited")
+}
+
+fn paragraph(id: &str, content: &str, has_children: bool) -> Block {
+    Block {
+        id: Some(id.to_string()),
+        has_children: Some(has_children),
+        block_type: BlockType::Paragraph {
+            paragraph: ParagraphValue {
+                rich_text: vec![RichText::Text {
+                    text: Text {
+                        content: content.to_string(),
+                        link: None,
+                    },
+                    annotations: None,
+                    plain_text: None,
+                    href: None,
+                }],
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    }
+}
+
+fn ids(blocks: &[BlockWithChildren]) -> Vec<String> {
+    blocks
+        .iter()
+        .flat_map(|b| {
+            std::iter::once(b.block.id.clone().unwrap_or_default()).chain(ids(&b.children))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn concurrent_fetch_preserves_notion_order() -> Result<()> {
+    // 先に投げたリクエストほど遅く返るので、完了順は Notion 上の順序と逆になる
+    let source = MockSource::default()
+        .children(
+            "page",
+            vec![
+                paragraph("a", "a", true),
+                paragraph("b", "b", true),
+                paragraph("c", "c", true),
+            ],
+        )
+        .delayed_children(
+            "a",
+            Duration::from_millis(60),
+            vec![paragraph("a1", "a1", false)],
+        )
+        .delayed_children(
+            "b",
+            Duration::from_millis(30),
+            vec![paragraph("b1", "b1", false)],
+        )
+        .children("c", vec![paragraph("c1", "c1", false)]);
+    let converter = builder(source).fetch_concurrency(3).build();
+
+    let blocks = converter.fetch_blocks("page").await?;
+    assert_eq!(ids(&blocks), vec!["a", "a1", "b", "b1", "c", "c1"]);
+
+    let markdown = converter.convert_page("page").await?;
+    let a = markdown.find('a').unwrap();
+    let b = markdown.find('b').unwrap();
+    let c = markdown.find('c').unwrap();
+    assert!(a < b && b < c, "unexpected order: {}", markdown);
+    Ok(())
+}
+
+#[tokio::test]
+async fn database_view_link_resolves_title() -> Result<()> {
+    let source = MockSource::default()
+        .children(
+            "page",
+            vec![Block {
+                id: Some("link".to_string()),
+                block_type: BlockType::LinkToPage {
+                    link_to_page: Parent::DatabaseId {
+                        database_id: "1234-abcd".to_string(),
+                    },
+                },
+                ..Default::default()
+            }],
+        )
+        .title("1234-abcd", "タスク一覧");
+    let converter = builder(source).build();
+
+    assert_eq!(
+        converter.convert_page("page").await?,
+        "[タスク一覧](https://www.notion.so/1234abcd)\n\n"
+    );
+    Ok(())
+}
+
+// "b" の子ブロックは登録しないので、取得すると権限エラー（404）になる
+fn restricted_source() -> MockSource {
+    MockSource::default().children(
+        "page",
+        vec![
+            paragraph("a", "a", false),
+            paragraph("b", "b", true),
+            paragraph("c", "c", false),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn inaccessible_children_fail_by_default() -> Result<()> {
+    let converter = builder(restricted_source()).build();
+
+    assert!(converter.convert_page("page").await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn inaccessible_children_are_skipped() -> Result<()> {
+    let converter = builder(restricted_source()).skip_inaccessible(true).build();
+
+    assert_eq!(
+        converter.convert_page("page").await?,
+        "a\nb\n  <!-- notion2md: inaccessible block b -->\nc\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_depth_stops_recursion() -> Result<()> {
+    let converter = builder(MockSource::chain(10)).max_depth(3).build();
+
+    let blocks = converter.fetch_blocks("page").await?;
+    assert_eq!(ids(&blocks), vec!["1", "2", "3"]);
+    Ok(())
+}
+
+fn bookmark(url: &str) -> Block {
+    Block {
+        block_type: BlockType::Bookmark {
+            bookmark: BookmarkValue {
+                caption: Vec::new(),
+                url: url.to_string(),
+            },
+        },
+        ..Default::default()
+    }
+}
+
+// example.com の Open Graph 情報を返す。"https://broken.example" の取得は失敗する
+fn with_open_graph(source: MockSource) -> MockSource {
+    let html = r#"<meta property="og:title" content="Example">
+<meta property="og:description" content="An example site">
+<meta property="og:image" content="https://example.com/og.png">"#;
+    let metadata = notion2md::utils::open_graph(html).expect("open graph");
+    source.link_metadata("https://example.com", metadata)
+}
+
+fn bookmark_source() -> MockSource {
+    with_open_graph(MockSource::default().children(
+        "page",
+        vec![
+            bookmark("https://example.com"),
+            bookmark("https://example.com"),
+            bookmark("https://broken.example"),
+        ],
+    ))
+}
+
+#[tokio::test]
+async fn bookmark_as_card_uses_open_graph() -> Result<()> {
+    let converter = builder(bookmark_source()).bookmark_as_card(true).build();
+
+    let card = "<a href=\"https://example.com\" class=\"bookmark-card\">\n\
+<img src=\"https://example.com/og.png\" alt=\"\">\n\
+<strong>Example</strong>\n\
+<p>An example site</p>\n\
+</a>\n\n";
+    assert_eq!(
+        converter.convert_page("page").await?,
+        format!(
+            "{}{}[https://broken.example](https://broken.example)\n\n",
+            card, card
+        )
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn bookmark_metadata_is_cached_by_url() -> Result<()> {
+    let source = Arc::new(bookmark_source());
+    let converter = builder(Shared(source.clone()))
+        .bookmark_as_card(true)
+        .build();
+
+    converter.fetch_blocks("page").await?;
+    // example.com は 1 回だけ、失敗した broken.example は 1 回
+    assert_eq!(source.metadata_calls.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+fn database_row(id: &str, name: &str, done: bool) -> Page {
+    serde_json::from_value(serde_json::json!({
+        "object": "page",
+        "id": id,
+        "created_time": "2024-01-01T00:00:00.000Z",
+        "last_edited_time": "2024-01-01T00:00:00.000Z",
+        "created_by": { "object": "user", "id": "user" },
+        "last_edited_by": { "object": "user", "id": "user" },
+        "archived": false,
+        "parent": { "type": "database_id", "database_id": "db" },
+        "url": format!("https://www.notion.so/{}", id),
+        "properties": {
+            "Name": {
+                "id": "title",
+                "type": "title",
+                "title": [{
+                    "type": "text",
+                    "text": { "content": name, "link": null },
+                    "plain_text": name,
+                    "href": null
+                }]
+            },
+            "Done": { "id": "done", "type": "checkbox", "checkbox": done }
+        }
+    }))
+    .expect("page")
+}
+
+// 2 ページに分かれたデータベースを返すモック。2 ページ目の最初の取得は 429 で失敗する
+#[derive(Default)]
+struct PaginatedDatabaseSource {
+    query_calls: AtomicUsize,
+}
+
+impl BlockSource for PaginatedDatabaseSource {
+    fn retrieve_block_children<'a>(
+        &'a self,
+        _block_id: &'a str,
+        _start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<RetrieveBlockChilerenResponse>> {
+        Box::pin(async { anyhow::bail!("not used") })
+    }
+
+    fn query_database<'a>(
+        &'a self,
+        _database_id: &'a str,
+        start_cursor: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<QueryDatabaseResponse>> {
+        Box::pin(async move {
+            let call = self.query_calls.fetch_add(1, Ordering::SeqCst);
+            let (results, next_cursor) = match start_cursor {
+                None => (vec![database_row("p1", "first", true)], Some("page-2")),
+                Some("page-2") if call == 1 => return Err(rate_limited()),
+                Some(_) => (vec![database_row("p2", "second", false)], None),
+            };
+            Ok(QueryDatabaseResponse {
+                object: "list".to_string(),
+                results,
+                has_more: next_cursor.is_some(),
+                next_cursor: next_cursor.map(str::to_string),
+            })
+        })
+    }
+}
+
+#[tokio::test]
+async fn database_pagination_retries_rate_limits() -> Result<()> {
+    let source = Arc::new(PaginatedDatabaseSource::default());
+    let converter = builder(Shared(source.clone()))
+        .retry_base_delay(Duration::from_millis(1))
+        .build();
+
+    let pages = converter.query_database("db").await?;
+    assert_eq!(
+        pages.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(),
+        vec!["p1", "p2"]
+    );
+    // 1 ページ目、429、2 ページ目の再試行
+    assert_eq!(source.query_calls.load(Ordering::SeqCst), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn convert_database_renders_property_table() -> Result<()> {
+    let converter = builder(PaginatedDatabaseSource::default())
+        .retry_base_delay(Duration::from_millis(1))
+        .build();
+
+    assert_eq!(
+        converter.convert_database("db").await?,
+        "| Name   | Done |\n| ------ | ---- |\n| first  | ✓  |\n| second | ✗  |\n\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn rate_limit_gives_up_after_max_retries() -> Result<()> {
+    let converter = builder(PaginatedDatabaseSource::default())
+        .max_retries(0)
+        .build();
+
+    assert!(converter.query_database("db").await.is_err());
+    Ok(())
+}
+
+fn comment(id: &str, content: &str) -> Comment {
+    serde_json::from_value(serde_json::json!({
+        "object": "comment",
+        "id": id,
+        "parent": { "type": "block_id", "block_id": "block" },
+        "discussion_id": "discussion",
+        "created_time": "2024-01-01T00:00:00.000Z",
+        "last_edited_time": "2024-01-01T00:00:00.000Z",
+        "created_by": {
+            "object": "user",
+            "id": "user",
+            "type": "person",
+            "person": { "email": "user@example.com" }
+        },
+        "rich_text": [{
+            "type": "text",
+            "text": { "content": content, "link": null },
+            "plain_text": content,
+            "href": null
+        }]
+    }))
+    .expect("comment")
+}
+
+#[tokio::test]
+async fn block_comments_are_fetched_from_source() -> Result<()> {
+    let source = MockSource::default()
+        .children("page", vec![paragraph("a", "本文", false)])
+        .comment(
+            "a",
+            comment("1234abcd-0000-0000-0000-000000000000", "要確認\n2 行目"),
+        )
+        .rate_limited_comments(1);
+    let converter = builder(source)
+        .include_comments(true)
+        .retry_base_delay(Duration::from_millis(1))
+        .build();
+
+    let blocks = converter.fetch_blocks("page").await?;
+    assert_eq!(blocks[0].comments.len(), 1);
+    assert_eq!(
+        converter.render_tree(&blocks)?,
+        "本文[^1234abcd000000000000000000000000]\n\n\
+         [^1234abcd000000000000000000000000]: 要確認\n    2 行目\n"
+    );
+    Ok(())
+}
+
+// Home → Docs の 2 階層で、どちらのページにもパンくずリストがある
+fn ancestry_source() -> MockSource {
+    let crumb = || Block {
+        id: Some("crumb".to_string()),
+        block_type: BlockType::Breadcrumb {
+            breadcrump: BreadcrumpValue {},
+        },
+        ..Default::default()
+    };
+    MockSource::default()
+        .children("home", vec![crumb()])
+        .children("docs", vec![crumb()])
+        .page(database_row("home", "Home", false))
+        .page(database_row("docs", "Docs", false))
+        .parent("home", Parent::Workspace { workspace: true })
+        .parent(
+            "docs",
+            Parent::PageId {
+                page_id: "home".to_string(),
+            },
+        )
+        .title("home", "Home")
+        .title("docs", "Docs")
+}
+
+#[tokio::test]
+async fn breadcrumb_renders_ancestry() -> Result<()> {
+    let converter = builder(ancestry_source()).build();
+
+    assert_eq!(
+        converter.convert_page("docs").await?,
+        "[Home](https://www.notion.so/home) / [Docs](https://www.notion.so/docs)\n\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn frontmatter_path_lists_ancestor_titles() -> Result<()> {
+    let converter = builder(ancestry_source()).frontmatter_path(true).build();
+
+    let (markdown, meta) = converter.convert_page_with_meta("docs").await?;
+    assert_eq!(meta.path, vec!["Home"]);
+    assert!(
+        markdown.starts_with("---\npath:\n  - Home\nDone: false\nName: Docs\n---\n\n"),
+        "{}",
+        markdown
+    );
+
+    // 最上位のページは祖先を持たない
+    let (markdown, _) = converter.convert_page_with_meta("home").await?;
+    assert!(markdown.starts_with("---\npath: []\n"), "{}", markdown);
+    Ok(())
+}
+
+// 外部 URL のカバー画像を持つページ
+fn cover_source() -> Result<MockSource> {
+    let mut page = database_row("post", "Post", false);
+    page.last_edited_time = "2024-03-05T12:30:00.000Z".parse()?;
+    page.cover = Some(serde_json::from_value(serde_json::json!({
+        "type": "external",
+        "external": { "url": "https://example.com/cover.png" }
+    }))?);
+    Ok(MockSource::default()
+        .children("post", vec![paragraph("body", "Body", false)])
+        .page(page))
+}
+
+#[tokio::test]
+async fn page_cover_as_image_or_frontmatter() -> Result<()> {
+    let converter = builder(cover_source()?)
+        .page_cover(PageCover::Image)
+        .build();
+    assert_eq!(
+        converter.convert_page("post").await?,
+        "![cover](https://example.com/cover.png)\n\nBody\n"
+    );
+
+    let converter = builder(cover_source()?)
+        .page_cover(PageCover::Frontmatter)
+        .build();
+    assert_eq!(
+        converter.convert_page("post").await?,
+        "---\ncover: https://example.com/cover.png\n---\n\nBody\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn frontmatter_dates_from_page_timestamps() -> Result<()> {
+    let converter = builder(cover_source()?).frontmatter_dates(true).build();
+    let (markdown, meta) = converter.convert_page_with_meta("post").await?;
+    assert_eq!(
+        markdown,
+        "---\ndate: 2024-01-01T00:00:00Z\nlastmod: 2024-03-05T12:30:00Z\n\
+         Done: false\nName: Post\n---\n\nBody\n"
+    );
+    assert_eq!(meta.title, "Post");
+    assert_eq!(meta.created_time.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+    assert_eq!(
+        meta.last_edited_time.to_rfc3339(),
+        "2024-03-05T12:30:00+00:00"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn properties_as_table_or_definition_list() -> Result<()> {
+    let converter = builder(cover_source()?)
+        .property_output(PropertyOutput::Table)
+        .build();
+    assert_eq!(
+        converter.convert_page("post").await?,
+        "| Property | Value |\n| --- | --- |\n| Done | ✗ |\n| Name | Post |\n\nBody\n"
+    );
+
+    let converter = builder(cover_source()?)
+        .property_output(PropertyOutput::DefinitionList)
+        .build();
+    assert_eq!(
+        converter.convert_page("post").await?,
+        "Done\n: ✗\n\nName\n: Post\n\nBody\n"
+    );
+    Ok(())
+}
+
+fn file_block(id: &str, block_type: BlockType) -> Block {
+    Block {
+        id: Some(id.to_string()),
+        has_children: Some(false),
+        block_type,
+        ..Default::default()
+    }
+}
+
+fn external(url: &str) -> File {
+    File::External {
+        external: ExternalFile {
+            url: url.to_string(),
+        },
+    }
+}
+
+#[tokio::test]
+async fn collect_assets_lists_file_blocks() -> Result<()> {
+    let source = MockSource::default()
+        .children(
+            "page",
+            vec![
+                file_block(
+                    "img",
+                    BlockType::Image {
+                        image: ImageValue {
+                            file_type: external("https://example.com/a.png"),
+                        },
+                    },
+                ),
+                paragraph("para", "text", true),
+                file_block(
+                    "vid",
+                    BlockType::Video {
+                        video: VideoValue {
+                            file_type: external("https://example.com/b.mp4"),
+                        },
+                    },
+                ),
+            ],
+        )
+        .children(
+            "para",
+            vec![
+                file_block(
+                    "doc",
+                    BlockType::Pdf {
+                        pdf: PdfValue {
+                            caption: Vec::new(),
+                            file_type: File::File {
+                                file: HostedFile {
+                                    url: "https://prod-files-secure.s3.amazonaws.com/c.pdf"
+                                        .to_string(),
+                                    expiry_time: "2024-01-01T00:00:00Z".parse()?,
+                                },
+                            },
+                        },
+                    },
+                ),
+                file_block(
+                    "zip",
+                    BlockType::File {
+                        file: FileValue {
+                            caption: Vec::new(),
+                            file_type: external("https://example.com/d.zip"),
+                            name: "d.zip".to_string(),
+                        },
+                    },
+                ),
+            ],
+        );
+    let converter = builder(source).build();
+
+    let asset = |block_id: &str, kind, url: &str| AssetRef {
+        block_id: block_id.to_string(),
+        kind,
+        url: url.to_string(),
+    };
+    assert_eq!(
+        converter.collect_assets("page").await?,
+        vec![
+            asset("img", AssetKind::Image, "https://example.com/a.png"),
+            asset(
+                "doc",
+                AssetKind::Pdf,
+                "https://prod-files-secure.s3.amazonaws.com/c.pdf"
+            ),
+            asset("zip", AssetKind::File, "https://example.com/d.zip"),
+            asset("vid", AssetKind::Video, "https://example.com/b.mp4"),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_blocks_show_fetched_size() -> Result<()> {
+    let file = |id: &str, name: &str| {
+        file_block(
+            id,
+            BlockType::File {
+                file: FileValue {
+                    caption: Vec::new(),
+                    file_type: external(&format!("https://example.com/{}", name)),
+                    name: name.to_string(),
+                },
+            },
+        )
+    };
+    // サイズが分かるのは report.pdf だけ
+    let source = MockSource::default()
+        .children(
+            "page",
+            vec![file("report", "report.pdf"), file("notes", "notes.txt")],
+        )
+        .file_size("https://example.com/report.pdf", 1_258_291);
+    let converter = builder(source).file_sizes(true).build();
+    assert_eq!(
+        converter.convert_page("page").await?,
+        "[report.pdf (1.2 MB)](https://example.com/report.pdf)\n\n\
+         [notes.txt](https://example.com/notes.txt)\n\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn link_preview_uses_open_graph_title() -> Result<()> {
+    let link_preview = |url: &str| Block {
+        block_type: BlockType::LinkPreview {
+            link_preview: LinkPreviewValue {
+                url: url.to_string(),
+            },
+        },
+        ..Default::default()
+    };
+    let source = with_open_graph(MockSource::default().children(
+        "page",
+        vec![
+            link_preview("https://example.com"),
+            link_preview("https://broken.example"),
+        ],
+    ));
+    let converter = builder(source).link_preview_titles(true).build();
+
+    assert_eq!(
+        converter.convert_page("page").await?,
+        "[Example](https://example.com)\n\n\
+         [https://broken.example](https://broken.example)\n\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn convert_database_to_csv_quotes_fields() -> Result<()> {
+    // カンマや改行を含む 2 行のデータベース
+    let source = MockSource::default().rows(vec![
+        database_row("row-1", "Smith, John", true),
+        database_row("row-2", "say \"hi\"\nbye", false),
+    ]);
+    let converter = builder(source).build();
+
+    assert_eq!(
+        converter.convert_database_to_csv("db").await?,
+        "Name,Done\n\"Smith, John\",✓\n\"say \"\"hi\"\"\nbye\",✗\n"
+    );
+    Ok(())
+}
+
+// relation のプロパティと太字のタイトルを持つ行と、関連ページのタイトルを返す
+fn relation_source() -> Result<MockSource> {
+    let mut row = database_row("row-1", "Release", false);
+    row.properties.insert(
+        "Name".to_string(),
+        serde_json::from_value(serde_json::json!({
+            "id": "title",
+            "type": "title",
+            "title": [{
+                "type": "text",
+                "text": { "content": "Release", "link": null },
+                "annotations": {
+                    "bold": true, "italic": false, "strikethrough": false,
+                    "underline": false, "code": false, "color": "default"
+                },
+                "plain_text": "Release",
+                "href": null
+            }]
+        }))?,
+    );
+    row.properties.insert(
+        "Docs".to_string(),
+        PageProperty::Relation {
+            id: None,
+            relation: ["page-a", "page-b"]
+                .into_iter()
+                .map(|id| RelationPropertyValue { id: id.to_string() })
+                .collect(),
+            has_more: Some(false),
+        },
+    );
+    Ok(MockSource::default()
+        .rows(vec![row])
+        .title("page-a", "Design")
+        .title("page-b", "Spec"))
+}
+
+#[tokio::test]
+async fn relation_properties_render_as_links() -> Result<()> {
+    let converter = builder(relation_source()?).build();
+    let markdown = converter.convert_database("db").await?;
+    assert!(markdown.contains("**Release**"), "{}", markdown);
+    assert!(
+        markdown.contains(
+            "[page-a](https://www.notion.so/pagea), [page-b](https://www.notion.so/pageb)"
+        ),
+        "{}",
+        markdown
+    );
+
+    let converter = builder(relation_source()?)
+        .resolve_relation_titles(true)
+        .build();
+    let markdown = converter.convert_database("db").await?;
+    assert!(
+        markdown
+            .contains("[Design](https://www.notion.so/pagea), [Spec](https://www.notion.so/pageb)"),
+        "{}",
+        markdown
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn convert_database_to_csv_uses_plain_text() -> Result<()> {
+    let converter = builder(relation_source()?).build();
+    assert_eq!(
+        converter.convert_database_to_csv("db").await?,
+        "Name,Docs,Done\nRelease,\"page-a, page-b\",✗\n"
+    );
+
+    let converter = builder(relation_source()?)
+        .resolve_relation_titles(true)
+        .build();
+    assert_eq!(
+        converter.convert_database_to_csv("db").await?,
+        "Name,Docs,Done\nRelease,\"Design, Spec\",✗\n"
+    );
+    Ok(())
+}
+
+// 別々の場所にあるブロックを ID で返す。a だけ子ブロックを持つ
+fn scattered_source() -> MockSource {
+    MockSource::default()
+        .block(paragraph("a", "first", true))
+        .block(paragraph("b", "second", false))
+        .children("a", vec![paragraph("a-1", "nested", false)])
+}
+
+#[tokio::test]
+async fn convert_block_ids_renders_blocks_in_order() -> Result<()> {
+    let converter = builder(scattered_source()).build();
+    assert_eq!(
+        converter.convert_block_ids(&["b", "a"]).await?,
+        "second\nfirst\n  nested\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn crlf_line_endings() -> Result<()> {
+    let converter = builder(scattered_source())
+        .line_ending(LineEnding::CrLf)
+        .build();
+    assert_eq!(
+        converter.convert_block_ids(&["b", "a"]).await?,
+        "second\r\nfirst\r\n  nested\r\n"
+    );
+    Ok(())
+}
+
+// リンクドデータベースとそのビューの設定
+fn database_view_source() -> MockSource {
+    MockSource::default()
+        .children(
+            "page",
+            vec![Block {
+                id: Some("db-block".to_string()),
+                block_type: BlockType::ChildDatabase {
+                    child_database: ChildDatabaseValue {
+                        title: "Tasks".to_string(),
+                    },
+                },
+                ..Default::default()
+            }],
+        )
+        .database_view(DatabaseView {
+            filter: Some("Status is Done".to_string()),
+            sorts: vec!["Due ascending".to_string(), "Name descending".to_string()],
+        })
+}
+
+#[tokio::test]
+async fn database_view_is_emitted_as_comment() -> Result<()> {
+    let link = "[Tasks](https://www.notion.so/dbblock)\n\n";
+
+    let converter = builder(database_view_source())
+        .database_view_comments(true)
+        .build();
+    let blocks = converter.fetch_blocks("page").await?;
+    assert_eq!(
+        converter.convert_blocks_to_markdown(&blocks)?,
+        format!(
+            "<!-- notion2md: filter: Status is Done; sort: Due ascending, Name descending -->\n{}",
+            link
+        )
+    );
+
+    // フラグが無効ならビューは取得しない
+    let converter = builder(database_view_source()).build();
+    let blocks = converter.fetch_blocks("page").await?;
+    assert_eq!(converter.convert_blocks_to_markdown(&blocks)?, link);
+    Ok(())
+}
+
+// 再帰せずにツリーの深さを数える
+fn tree_depth(tree: &[BlockWithChildren]) -> usize {
+    let mut depth = 0;
+    let mut level = tree;
+    while let Some(block) = level.first() {
+        depth += 1;
+        level = &block.children;
+    }
+    depth
+}
+
+#[tokio::test]
+async fn iterative_fetch_handles_deep_trees() -> Result<()> {
+    let converter = builder(MockSource::chain(1000))
+        .iterative_fetch(true)
+        .build();
+    let blocks = converter.fetch_blocks("page").await?;
+    assert_eq!(tree_depth(&blocks), 1000);
+
+    // 変換は再帰するので、README に書いた深さ（既定の 2 MiB のスタックで 500 階層）を確かめる
+    let subtree = (0..500).fold(&blocks[..], |level, _| &level[0].children[..]);
+    let markdown = std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn_scoped(scope, || converter.render_tree(subtree))
+            .expect("thread")
+            .join()
+            .expect("render")
+    })?;
+    let lines = markdown.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 500);
+    assert_eq!(lines[0], "level 501");
+    assert_eq!(lines[499], format!("{}level 1000", "  ".repeat(499)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn iterative_fetch_matches_recursive_fetch() -> Result<()> {
+    let recursive = builder(MockSource::chain(5)).max_depth(3).build();
+    let iterative = builder(MockSource::chain(5))
+        .max_depth(3)
+        .iterative_fetch(true)
+        .build();
+
+    let expected = recursive.render_tree(&recursive.fetch_blocks("page").await?)?;
+    assert_eq!(expected, "level 1\n  level 2\n    level 3\n");
+    let blocks = iterative.fetch_blocks("page").await?;
+    assert_eq!(iterative.render_tree(&blocks)?, expected);
+    Ok(())
+}
+
+// ステータスに色のついた行を 1 件返すデータベース
+fn status_source() -> MockSource {
+    let mut row = database_row("row-1", "Release", true);
+    row.properties.insert(
+        "Status".to_string(),
+        PageProperty::Status {
+            id: None,
+            status: Some(SelectPropertyValue {
+                id: None,
+                name: Some("Shipped".to_string()),
+                color: Some(Color::Green),
+            }),
+        },
+    );
+    MockSource::default().rows(vec![row])
+}
+
+#[tokio::test]
+async fn colored_status_renders_as_badge() -> Result<()> {
+    let badge = "<span style=\"background-color: green\">Shipped</span>";
+
+    let converter = builder(status_source()).render_color(true).build();
+    assert!(converter.convert_database("db").await?.contains(badge));
+    // CSV には色を付けない
+    assert!(!converter
+        .convert_database_to_csv("db")
+        .await?
+        .contains(badge));
+
+    let converter = builder(status_source()).build();
+    let markdown = converter.convert_database("db").await?;
+    assert!(markdown.contains("| Shipped"), "{}", markdown);
+    assert!(!markdown.contains("<span"));
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use notion2md::builder::NotionToMarkdownBuilder;
+use notion2md::notion_client::block::*;
+use notion2md::notion_client::Client;
+use notion2md::notion_to_md::BlockWithChildren;
+use std::sync::Mutex;
+
+// ログを記録するロガー。ロガーはプロセスで 1 つしか登録できないので、ログを確認するテストはこのファイルの 1 つにまとめる
+struct CaptureLogger;
+
+static RECORDS: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if record.target().starts_with("notion2md") {
+            RECORDS
+                .lock()
+                .unwrap()
+                .push((record.level(), record.args().to_string()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn take_records() -> Vec<(Level, String)> {
+    std::mem::take(&mut *RECORDS.lock().unwrap())
+}
+
+fn block(block_type: BlockType) -> BlockWithChildren {
+    BlockWithChildren {
+        block: Block {
+            block_type,
+            ..Default::default()
+        },
+        children: Vec::new(),
+        comments: Vec::new(),
+    }
+}
+
+fn unsupported_blocks() -> Vec<BlockWithChildren> {
+    vec![
+        block(BlockType::Unsupported),
+        block(BlockType::Unsupported),
+        block(BlockType::Unsupported),
+        block(BlockType::None),
+    ]
+}
+
+#[test]
+fn unsupported_block_logging() -> Result<()> {
+    log::set_boxed_logger(Box::new(CaptureLogger)).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+    let client = || Client::new("dummy-token".to_string(), None).expect("client");
+
+    // 既定では 1 ブロックごとに warn
+    let converter = NotionToMarkdownBuilder::new(client()).build();
+    converter.convert_blocks_to_markdown(&unsupported_blocks())?;
+    let records = take_records();
+    assert_eq!(records.len(), 4);
+    assert!(records.iter().all(|(level, _)| *level == Level::Warn));
+
+    // 種類ごとにまとめる
+    let converter = NotionToMarkdownBuilder::new(client())
+        .unsupported_log_level(Some(Level::Debug))
+        .summarize_unsupported(true)
+        .build();
+    converter.convert_blocks_to_markdown(&unsupported_blocks())?;
+    assert_eq!(
+        take_records(),
+        vec![
+            (
+                Level::Debug,
+                "1 unsupported blocks of type None".to_string()
+            ),
+            (
+                Level::Debug,
+                "3 unsupported blocks of type Unsupported".to_string()
+            ),
+        ]
+    );
+
+    // 記録しない
+    let converter = NotionToMarkdownBuilder::new(client())
+        .unsupported_log_level(None)
+        .build();
+    converter.convert_blocks_to_markdown(&unsupported_blocks())?;
+    assert!(take_records().is_empty());
+    Ok(())
+}